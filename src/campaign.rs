@@ -0,0 +1,234 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ron_file, FileError, Level, LevelError, Program, Score, World};
+
+/// The levels of the game in the order they are played. Each level is a file in one directory,
+/// ordered by file name, so `01_first_steps.ron` comes before `02_plate_and_door.ron`.
+#[derive(Debug)]
+pub struct Campaign {
+    pub levels: Vec<CampaignLevel>,
+    /// Level files that couldn't be loaded and were left out, so one broken file doesn't take
+    /// the rest of the campaign down with it.
+    pub skipped: Vec<CampaignError>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CampaignLevel {
+    /// The level's file name without the extension, which identifies it in `Progress`.
+    pub name: String,
+    pub level: Level,
+}
+
+#[derive(Debug)]
+pub enum CampaignError {
+    /// The directory holding the levels couldn't be read.
+    Dir(FileError),
+    /// The level file at `path` couldn't be loaded.
+    Level { path: PathBuf, error: LevelError },
+}
+
+impl fmt::Display for CampaignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CampaignError::Dir(error) => write!(f, "campaign: {error}"),
+            CampaignError::Level { path, error } => write!(f, "{}: {error}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for CampaignError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CampaignError::Dir(error) => Some(error),
+            CampaignError::Level { error, .. } => Some(error),
+        }
+    }
+}
+
+impl From<FileError> for CampaignError {
+    fn from(error: FileError) -> Self {
+        CampaignError::Dir(error)
+    }
+}
+
+/// Which levels the player has solved, and the programs they solved them with.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Progress {
+    pub solved: BTreeMap<String, Solution>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Solution {
+    /// One program for each robot in the level, from the latest solve.
+    pub programs: Vec<Program>,
+    /// The best value of each metric over every solve of the level.
+    #[serde(default)]
+    pub best: Option<Score>,
+}
+
+impl Campaign {
+    /// Loads every `.ron` file in `dir` as a level. Only fails if `dir` can't be read.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Campaign, CampaignError> {
+        let mut paths = vec![];
+        for entry in fs::read_dir(dir).map_err(FileError::Io)? {
+            let path = entry.map_err(FileError::Io)?.path();
+            if path.extension().is_some_and(|extension| extension == "ron") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        let mut levels = vec![];
+        let mut skipped = vec![];
+        for path in paths {
+            let level = match Level::load(&path) {
+                Ok(level) => level,
+                Err(error) => {
+                    skipped.push(CampaignError::Level { path, error });
+                    continue;
+                }
+            };
+            levels.push(CampaignLevel {
+                name: path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                level,
+            });
+        }
+        Ok(Campaign { levels, skipped })
+    }
+    /// The first level is always unlocked, and every other one once the level before it has
+    /// been solved.
+    pub fn is_unlocked(&self, index: usize, progress: &Progress) -> bool {
+        index == 0
+            || self
+                .levels
+                .get(index - 1)
+                .is_some_and(|previous| progress.is_solved(&previous.name))
+    }
+}
+
+impl CampaignLevel {
+    /// The name shown to the player, e.g. "First steps" for `01_first_steps.ron`.
+    pub fn title(&self) -> String {
+        let name = self
+            .name
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .trim_start_matches('_')
+            .replace('_', " ");
+        let mut chars = name.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => self.name.clone(),
+        }
+    }
+}
+
+impl Progress {
+    /// Loads the player's progress, starting fresh if nothing has been saved yet.
+    pub fn load(path: impl AsRef<Path>) -> Result<Progress, FileError> {
+        match ron_file::load(path) {
+            Err(FileError::Io(error)) if error.kind() == ErrorKind::NotFound => {
+                Ok(Progress::default())
+            }
+            result => result,
+        }
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FileError> {
+        ron_file::save(self, path)
+    }
+    pub fn is_solved(&self, name: &str) -> bool {
+        self.solved.contains_key(name)
+    }
+    pub fn best_score(&self, name: &str) -> Option<Score> {
+        self.solved.get(name).and_then(|solution| solution.best)
+    }
+    /// Marks the level `name` as solved with the programs currently in `world`, keeping the best
+    /// score so far.
+    pub fn record(&mut self, name: &str, world: &World) {
+        let score = world.score();
+        let best = self.best_score(name).map_or(score, |best| best.best(score));
+        self.solved.insert(
+            name.to_string(),
+            Solution {
+                programs: world
+                    .robots
+                    .iter()
+                    .map(|robot| robot.brain.to_program())
+                    .collect(),
+                best: Some(best),
+            },
+        );
+    }
+    /// Puts the programs the level `name` was solved with back into the robots of `world`.
+    /// Programs that no longer fit the level are skipped.
+    pub fn restore(&self, name: &str, world: &mut World) {
+        let Some(solution) = self.solved.get(name) else {
+            return;
+        };
+        for (robot, program) in world.robots.iter_mut().zip(&solution.programs) {
+            let _ = robot.brain.apply_program(program.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{Campaign, CampaignError, FileError, LevelError};
+
+    const LEVEL: &str = "(
+        map: (width: 1, height: 1, cells: [Empty]),
+        robots: [(pos: (0, 0), rotation: Up, brain_width: 1, brain_height: 1, total_instructions: [])],
+    )";
+
+    #[test]
+    fn broken_levels_are_skipped() {
+        let dir = std::env::temp_dir().join("robot_coding_game_campaign_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("01_good.ron"), LEVEL).unwrap();
+        fs::write(dir.join("02_unparsable.ron"), "(map:").unwrap();
+        fs::write(
+            dir.join("03_invalid.ron"),
+            LEVEL.replace("(0, 0)", "(5, 5)"),
+        )
+        .unwrap();
+        let campaign = Campaign::load(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(campaign.levels.len(), 1);
+        assert_eq!(campaign.levels[0].name, "01_good");
+        assert!(matches!(
+            campaign.skipped[..],
+            [
+                CampaignError::Level {
+                    error: LevelError::File(FileError::Parse(_)),
+                    ..
+                },
+                CampaignError::Level {
+                    error: LevelError::Invalid(_),
+                    ..
+                },
+            ]
+        ));
+    }
+
+    #[test]
+    fn missing_directory_fails() {
+        let dir = std::env::temp_dir().join("robot_coding_game_missing_campaign");
+        assert!(matches!(
+            Campaign::load(dir),
+            Err(CampaignError::Dir(FileError::Io(_)))
+        ));
+    }
+}
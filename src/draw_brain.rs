@@ -1,11 +1,14 @@
 use raylib::prelude::*;
-use rodio::buffer;
+use robot_coding_game::{Brain, Instruction, InstructionType, Item, Rotation};
 
-use crate::{Assets, Brain, Instruction, InstructionType, Rotation};
+use crate::{draw_item, Assets, BrainEdit, CHANNEL_COLORS};
 
 pub fn draw_brain(
     d: &mut RaylibDrawHandle,
     brain: &Brain,
+    carrying: Option<Item>,
+    selection: Option<((i32, i32), (i32, i32))>,
+    highlight: Option<(i32, i32)>,
     bottom_left_pos: Vector2,
     size: f32,
     assets: &Assets,
@@ -23,7 +26,10 @@ pub fn draw_brain(
         (size - assets.brain_corner.width as f32 * scale * 2.0) / brain.width as f32
             * brain.height as f32
             + assets.brain_corner.width as f32 * scale * 2.0,
-        "Brain".to_string(),
+        match brain.wrap {
+            true => "Brain (wrap)".to_string(),
+            false => "Brain".to_string(),
+        },
     );
     let width = size - buffer_x * 2.0;
     let instruction_size = width / brain.width as f32;
@@ -32,6 +38,36 @@ pub fn draw_brain(
         bottom_left_pos.x + buffer_x,
         bottom_left_pos.y - buffer_y - height,
     );
+    //Counters
+    let counters = brain
+        .counters
+        .iter()
+        .enumerate()
+        .map(|(i, value)| format!("{}: {value}", i + 1))
+        .collect::<Vec<String>>()
+        .join("   ");
+    let font_size = 10.0 * scale;
+    d.draw_text(
+        counters.as_str(),
+        top_left_pos.x as i32,
+        (top_left_pos.y - buffer_y - font_size - 2.0 * scale) as i32,
+        font_size as i32,
+        Color::new(170, 80, 255, 255),
+    );
+    //Carried item
+    if let Some(item) = carrying {
+        let item_size = font_size * 1.5;
+        draw_item(
+            d,
+            item,
+            Rectangle {
+                x: top_left_pos.x + width - item_size,
+                y: top_left_pos.y - buffer_y - item_size,
+                width: item_size,
+                height: item_size,
+            },
+        );
+    }
     //Brain
     for (i, instruction) in brain.instructions.iter().enumerate() {
         let grid_pos = Vector2::new(
@@ -77,6 +113,30 @@ pub fn draw_brain(
             );
         }
     }
+    //Highlight
+    if let Some((x, y)) = highlight {
+        let rec = Rectangle {
+            x: x as f32 * instruction_size + top_left_pos.x,
+            y: (-y as f32 - 1.0) * instruction_size + top_left_pos.y + height,
+            width: instruction_size,
+            height: instruction_size,
+        };
+        d.draw_rectangle_rec(rec, Color::new(255, 220, 60, 60));
+        d.draw_rectangle_lines_ex(rec, 2.0 * scale, Color::new(255, 220, 60, 255));
+    }
+    //Selection
+    if let Some(((x0, y0), (x1, y1))) = selection {
+        d.draw_rectangle_lines_ex(
+            Rectangle {
+                x: x0.min(x1) as f32 * instruction_size + top_left_pos.x,
+                y: (-y0.max(y1) as f32 - 1.0) * instruction_size + top_left_pos.y + height,
+                width: ((x0 - x1).abs() + 1) as f32 * instruction_size,
+                height: ((y0 - y1).abs() + 1) as f32 * instruction_size,
+            },
+            2.0 * scale,
+            Color::new(255, 255, 255, 200),
+        );
+    }
     let mouse_pos_brain = Vector2::new(
         ((mouse_pos.x - top_left_pos.x) / instruction_size).floor(),
         (-(mouse_pos.y - top_left_pos.y - height) / instruction_size).floor(),
@@ -135,12 +195,14 @@ pub fn draw_brain(
                     instruction_type: avalible_instructions[i].1,
                     rotation: selected_instruction.rotation,
                     edit: false,
+                    channel: selected_instruction.channel,
                 }
             } else {
                 Instruction {
                     instruction_type: avalible_instructions[i].1,
                     rotation: Rotation::Up,
                     edit: false,
+                    channel: 0,
                 }
             }
         } else {
@@ -148,6 +210,7 @@ pub fn draw_brain(
                 instruction_type: InstructionType::Blank,
                 rotation: Rotation::Up,
                 edit: false,
+                channel: 0,
             }
         };
 
@@ -167,6 +230,40 @@ pub fn draw_brain(
     }
 }
 
+/// The screen area covered by `draw_brain`, including the selection panel below the brain.
+pub fn brain_panel_rect(brain: &Brain, brain_edit: &BrainEdit, assets: &Assets) -> Rectangle {
+    let size = brain_edit.size * brain_edit.scale;
+    let buffer_x = assets.brain_corner.width as f32 * brain_edit.scale;
+    let buffer_y = assets.brain_corner.height as f32 * brain_edit.scale;
+    let width = size - buffer_x * 2.0;
+    let brain_height = width / brain.width as f32 * brain.height as f32 + buffer_x * 2.0;
+    let selection_grid_height = (brain.get_avalible_instructions().len() as f32 / 5.0).ceil();
+    let selection_height = selection_grid_height * width / 5.0;
+    Rectangle {
+        x: brain_edit.pos.x,
+        y: brain_edit.pos.y - brain_height,
+        width: size,
+        height: brain_height + buffer_y * 3.0 + selection_height,
+    }
+}
+
+/// Maps a screen position to the brain cell `draw_brain` draws under it.
+pub fn brain_cell_at(
+    brain: &Brain,
+    brain_edit: &BrainEdit,
+    assets: &Assets,
+    mouse_pos: Vector2,
+) -> (i32, i32) {
+    let buffer_x = assets.brain_corner.width as f32 * brain_edit.scale;
+    let buffer_y = assets.brain_corner.height as f32 * brain_edit.scale;
+    let width = brain_edit.size * brain_edit.scale - buffer_x * 2.0;
+    let instruction_size = width / brain.width as f32;
+    (
+        ((mouse_pos.x - (brain_edit.pos.x + buffer_x)) / instruction_size).floor() as i32,
+        (-((mouse_pos.y - (brain_edit.pos.y - buffer_y)) / instruction_size)).floor() as i32,
+    )
+}
+
 fn draw_instruction(
     d: &mut RaylibDrawHandle,
     instruction: &Instruction,
@@ -176,30 +273,73 @@ fn draw_instruction(
     up: bool,
 ) {
     let mut rotation = match instruction.rotation {
-        crate::Rotation::Up => 0.0,
-        crate::Rotation::Right => 90.0,
-        crate::Rotation::Down => 180.0,
-        crate::Rotation::Left => 270.0,
+        Rotation::Up => 0.0,
+        Rotation::Right => 90.0,
+        Rotation::Down => 180.0,
+        Rotation::Left => 270.0,
     };
+    let mut tint = Color::WHITE;
     let texture = match instruction.instruction_type {
-        crate::InstructionType::Move => {
+        InstructionType::Move => {
             rotation = 0.0;
             &assets.move_instruction
         }
-        crate::InstructionType::Back => {
+        InstructionType::Back => {
             rotation = 0.0;
             &assets.back_instruction
         }
-        crate::InstructionType::Direction => &assets.direction_instruction,
-        crate::InstructionType::RotateLeft => {
+        InstructionType::Direction => &assets.direction_instruction,
+        InstructionType::WallAhead => &assets.wall_sensor_instruction,
+        InstructionType::GapAhead => &assets.gap_sensor_instruction,
+        InstructionType::OnGoal => &assets.goal_sensor_instruction,
+        InstructionType::RotateLeft => {
             rotation = 0.0;
             &assets.left_instruction
         }
-        crate::InstructionType::RotateRight => {
+        InstructionType::RotateRight => {
             rotation = 0.0;
             &assets.right_instruction
         }
-        crate::InstructionType::None => &assets.blank_instruction,
+        InstructionType::Increment => {
+            rotation = 0.0;
+            &assets.increment_instruction
+        }
+        InstructionType::Decrement => {
+            rotation = 0.0;
+            &assets.decrement_instruction
+        }
+        InstructionType::BranchIfZero => &assets.branch_zero_instruction,
+        InstructionType::Wait => {
+            rotation = 0.0;
+            &assets.wait_instruction
+        }
+        InstructionType::Halt => {
+            rotation = 0.0;
+            &assets.halt_instruction
+        }
+        InstructionType::Jump => {
+            rotation = 0.0;
+            &assets.jump_instruction
+        }
+        InstructionType::Signal => {
+            rotation = 0.0;
+            tint = CHANNEL_COLORS[instruction.channel];
+            &assets.signal_instruction
+        }
+        InstructionType::WaitForSignal => {
+            rotation = 0.0;
+            tint = CHANNEL_COLORS[instruction.channel];
+            &assets.wait_signal_instruction
+        }
+        InstructionType::Grab => {
+            rotation = 0.0;
+            &assets.grab_instruction
+        }
+        InstructionType::Drop => {
+            rotation = 0.0;
+            &assets.drop_instruction
+        }
+        InstructionType::None => &assets.blank_instruction,
         InstructionType::Blank => &assets.blank_instruction,
     };
     let offset = Vector2::new(instruction_size / 2.0, instruction_size / 2.0);
@@ -241,8 +381,34 @@ fn draw_instruction(
         },
         offset,
         rotation,
-        Color::WHITE,
+        tint,
     );
+    if matches!(
+        instruction.instruction_type,
+        InstructionType::Increment
+            | InstructionType::Decrement
+            | InstructionType::BranchIfZero
+            | InstructionType::Jump
+            | InstructionType::Signal
+            | InstructionType::WaitForSignal
+    ) {
+        d.draw_text(
+            (instruction.channel + 1).to_string().as_str(),
+            (pos.x + instruction_size * 0.12) as i32,
+            (pos.y + instruction_size * 0.08) as i32,
+            (instruction_size * 0.25) as i32,
+            CHANNEL_COLORS[instruction.channel],
+        );
+    }
+    if instruction.instruction_type == InstructionType::Wait {
+        d.draw_text(
+            instruction.wait_ticks().to_string().as_str(),
+            (pos.x + instruction_size * 0.4) as i32,
+            (pos.y + instruction_size * 0.33) as i32,
+            (instruction_size * 0.35) as i32,
+            Color::WHITE,
+        );
+    }
 }
 
 fn draw_ui_boarders(
@@ -1,6 +1,17 @@
 use raylib::prelude::*;
 
-use crate::{Assets, Cell, Map, World};
+use robot_coding_game::{Cell, Item, Object, ObjectKind, Robot, Rotation, World, CHANNELS};
+
+use crate::Assets;
+
+/// Colours anything that uses a channel, so matching keys, doors, plates and instructions can be
+/// picked out.
+pub const CHANNEL_COLORS: [Color; CHANNELS] = [
+    Color::new(220, 80, 80, 255),
+    Color::new(80, 140, 230, 255),
+    Color::new(230, 200, 60, 255),
+    Color::new(170, 90, 210, 255),
+];
 
 pub fn draw_board(
     d: &mut RaylibDrawHandle,
@@ -9,6 +20,7 @@ pub fn draw_board(
     pos: Vector2,
     width: f32,
     scale: f32,
+    step_fraction: f32,
 ) {
     let width = width * scale;
     let cell_size = width / world.map.width as f32;
@@ -29,6 +41,211 @@ pub fn draw_board(
             scale,
         );
     }
+    for &((x, y), item) in &world.map.items {
+        let rec = Rectangle {
+            x: pos.x + x as f32 * cell_size,
+            y: pos.y + (world.map.height as i32 - y) as f32 * cell_size,
+            width: cell_size,
+            height: cell_size,
+        };
+        draw_item(d, item, rec);
+    }
+    for object in &world.objects {
+        draw_object(d, world, object, pos, cell_size, step_fraction);
+    }
+    for robot in &world.robots {
+        draw_robot(d, assets, world, robot, pos, cell_size, step_fraction);
+    }
+}
+
+/// Maps a screen position to the map cell `draw_board` draws under it.
+pub fn board_cell_at(
+    world: &World,
+    pos: Vector2,
+    width: f32,
+    scale: f32,
+    screen_pos: Vector2,
+) -> (i32, i32) {
+    let cell_size = width * scale / world.map.width as f32;
+    (
+        ((screen_pos.x - pos.x) / cell_size).floor() as i32,
+        world.map.height as i32 - ((screen_pos.y - pos.y) / cell_size).floor() as i32,
+    )
+}
+
+fn draw_robot(
+    d: &mut RaylibDrawHandle,
+    assets: &Assets,
+    world: &World,
+    robot: &Robot,
+    board_pos: Vector2,
+    cell_size: f32,
+    step_fraction: f32,
+) {
+    let t = step_fraction.clamp(0.0, 1.0);
+    let grid_pos = Vector2::new(
+        robot.last_pos.0 as f32 + (robot.pos.0 - robot.last_pos.0) as f32 * t,
+        robot.last_pos.1 as f32 + (robot.pos.1 - robot.last_pos.1) as f32 * t,
+    );
+    let last_angle = rotation_angle(robot.last_rotation);
+    let turn = (rotation_angle(robot.rotation) - last_angle + 540.0) % 360.0 - 180.0;
+    let angle = last_angle + turn * t;
+    //Robots that fell into a gap shrink away
+    let size = if world.map.get_cell_type(robot.pos.0, robot.pos.1) == Cell::Gap {
+        cell_size * (1.0 - t)
+    } else {
+        cell_size
+    };
+    let center = Vector2::new(
+        board_pos.x + grid_pos.x * cell_size + cell_size / 2.0,
+        board_pos.y + (world.map.height as f32 - grid_pos.y) * cell_size + cell_size / 2.0,
+    );
+    d.draw_texture_pro(
+        &assets.robot,
+        Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: assets.robot.width as f32,
+            height: assets.robot.height as f32,
+        },
+        Rectangle {
+            x: center.x,
+            y: center.y,
+            width: size,
+            height: size,
+        },
+        Vector2::new(size / 2.0, size / 2.0),
+        angle,
+        Color::WHITE,
+    );
+    if let Some(item) = robot.carrying {
+        draw_item(
+            d,
+            item,
+            Rectangle {
+                x: center.x - size * 0.3,
+                y: center.y - size * 0.3,
+                width: size * 0.6,
+                height: size * 0.6,
+            },
+        );
+    }
+}
+
+/// Draws `item` centred in `rec`.
+pub fn draw_item(d: &mut RaylibDrawHandle, item: Item, rec: Rectangle) {
+    let center = Vector2::new(rec.x + rec.width / 2.0, rec.y + rec.height / 2.0);
+    match item {
+        Item::Gem => {
+            d.draw_poly(
+                center,
+                4,
+                rec.width * 0.3,
+                0.0,
+                Color::new(60, 220, 220, 255),
+            );
+            d.draw_poly_lines(
+                center,
+                4,
+                rec.width * 0.3,
+                0.0,
+                Color::new(30, 110, 110, 255),
+            );
+        }
+        Item::Battery => {
+            let body = Rectangle {
+                x: center.x - rec.width * 0.15,
+                y: center.y - rec.height * 0.25,
+                width: rec.width * 0.3,
+                height: rec.height * 0.5,
+            };
+            d.draw_rectangle_rec(
+                Rectangle {
+                    x: center.x - rec.width * 0.07,
+                    y: body.y - rec.height * 0.06,
+                    width: rec.width * 0.14,
+                    height: rec.height * 0.06,
+                },
+                Color::new(200, 200, 200, 255),
+            );
+            d.draw_rectangle_rec(body, Color::new(90, 200, 110, 255));
+            d.draw_rectangle_lines_ex(body, rec.width * 0.04, Color::new(45, 100, 55, 255));
+        }
+    }
+}
+
+fn draw_object(
+    d: &mut RaylibDrawHandle,
+    world: &World,
+    object: &Object,
+    board_pos: Vector2,
+    cell_size: f32,
+    step_fraction: f32,
+) {
+    let t = step_fraction.clamp(0.0, 1.0);
+    let grid_pos = Vector2::new(
+        object.last_pos.0 as f32 + (object.pos.0 - object.last_pos.0) as f32 * t,
+        object.last_pos.1 as f32 + (object.pos.1 - object.last_pos.1) as f32 * t,
+    );
+    let pos = Vector2::new(
+        board_pos.x + grid_pos.x * cell_size,
+        board_pos.y + (world.map.height as f32 - grid_pos.y) * cell_size,
+    );
+    let inset = |amount: f32| Rectangle {
+        x: pos.x + cell_size * amount,
+        y: pos.y + cell_size * amount,
+        width: cell_size * (1.0 - amount * 2.0),
+        height: cell_size * (1.0 - amount * 2.0),
+    };
+    match object.kind {
+        ObjectKind::Crate => {
+            d.draw_rectangle_rec(inset(0.1), Color::new(150, 100, 50, 255));
+            d.draw_rectangle_lines_ex(inset(0.1), cell_size * 0.08, Color::new(90, 60, 30, 255));
+        }
+        ObjectKind::Key { channel } => {
+            let color = CHANNEL_COLORS[channel];
+            d.draw_circle_v(
+                Vector2::new(pos.x + cell_size * 0.35, pos.y + cell_size * 0.5),
+                cell_size * 0.15,
+                color,
+            );
+            d.draw_rectangle_rec(
+                Rectangle {
+                    x: pos.x + cell_size * 0.45,
+                    y: pos.y + cell_size * 0.45,
+                    width: cell_size * 0.35,
+                    height: cell_size * 0.1,
+                },
+                color,
+            );
+        }
+        ObjectKind::Door { channel, open } => {
+            let color = CHANNEL_COLORS[channel];
+            if open {
+                d.draw_rectangle_lines_ex(inset(0.0), cell_size * 0.08, color);
+            } else {
+                d.draw_rectangle_rec(inset(0.0), Color::new(70, 70, 70, 255));
+                d.draw_rectangle_lines_ex(inset(0.0), cell_size * 0.15, color);
+            }
+        }
+        ObjectKind::Plate { channel } => {
+            let color = if world.plate_pressed(channel) {
+                CHANNEL_COLORS[channel]
+            } else {
+                CHANNEL_COLORS[channel].fade(0.5)
+            };
+            d.draw_rectangle_rec(inset(0.25), color);
+        }
+    }
+}
+
+fn rotation_angle(rotation: Rotation) -> f32 {
+    match rotation {
+        Rotation::Up => 0.0,
+        Rotation::Right => 90.0,
+        Rotation::Down => 180.0,
+        Rotation::Left => 270.0,
+    }
 }
 
 fn draw_cell(
@@ -43,7 +260,7 @@ fn draw_cell(
     let center_cell = world.map.get_cell_type(grid_pos.0, grid_pos.1);
     let mut textures: Vec<&Texture2D> = vec![];
     match center_cell {
-        crate::Cell::Empty => {
+        Cell::Empty | Cell::Goal => {
             d.draw_rectangle_rec(
                 Rectangle {
                     x: pos.x,
@@ -53,33 +270,65 @@ fn draw_cell(
                 },
                 Color::new(100, 100, 100, 255),
             );
+            if center_cell == Cell::Goal {
+                d.draw_rectangle_lines_ex(
+                    Rectangle {
+                        x: pos.x + size * 0.2,
+                        y: pos.y + size * 0.2,
+                        width: size * 0.6,
+                        height: size * 0.6,
+                    },
+                    size * 0.08,
+                    Color::new(90, 200, 110, 255),
+                );
+            }
 
             match world.map.get_cell_type(grid_pos.0, grid_pos.1 - 1) {
-                Cell::Empty => {}
+                Cell::Empty | Cell::Goal => {}
                 Cell::Wall => textures.push(&assets.map.empty.shade_edge_bottom),
-                Cell::Gap => todo!(),
+                Cell::Gap => {}
             }
             match world.map.get_cell_type(grid_pos.0 + 1, grid_pos.1) {
-                Cell::Empty => {}
+                Cell::Empty | Cell::Goal => {}
                 Cell::Wall => textures.push(&assets.map.empty.shade_edge_right),
-                Cell::Gap => todo!(),
+                Cell::Gap => {}
             }
             match world.map.get_cell_type(grid_pos.0 + 1, grid_pos.1 - 1) {
-                Cell::Empty => {}
+                Cell::Empty | Cell::Goal => {}
                 Cell::Wall => textures.push(&assets.map.empty.shade_corner_filled),
-                Cell::Gap => todo!(),
+                Cell::Gap => {}
+            }
+            //Floor edge along gaps
+            let gap_top = world.map.get_cell_type(grid_pos.0, grid_pos.1 + 1) == Cell::Gap;
+            let gap_left = world.map.get_cell_type(grid_pos.0 - 1, grid_pos.1) == Cell::Gap;
+            if gap_top {
+                textures.push(&assets.map.empty.top);
+            }
+            if gap_left {
+                textures.push(&assets.map.empty.left);
+            }
+            if !gap_top
+                && !gap_left
+                && world.map.get_cell_type(grid_pos.0 - 1, grid_pos.1 + 1) == Cell::Gap
+            {
+                textures.push(&assets.map.empty.top_left);
             }
             if world.map.get_cell_type(grid_pos.0, grid_pos.1 - 1) == Cell::Wall
-                && world.map.get_cell_type(grid_pos.0 + 1, grid_pos.1 - 1) == Cell::Empty
+                && matches!(
+                    world.map.get_cell_type(grid_pos.0 + 1, grid_pos.1 - 1),
+                    Cell::Empty | Cell::Goal
+                )
             {
                 textures.push(&assets.map.empty.shade_corner_bottom)
             }
             if world.map.get_cell_type(grid_pos.0 + 1, grid_pos.1) == Cell::Wall
-                && world.map.get_cell_type(grid_pos.0 + 1, grid_pos.1 - 1) == Cell::Empty
+                && matches!(
+                    world.map.get_cell_type(grid_pos.0 + 1, grid_pos.1 - 1),
+                    Cell::Empty | Cell::Goal
+                )
             {
                 textures.push(&assets.map.empty.shade_corner_right)
             }
-
         }
         Cell::Wall => {
             d.draw_rectangle_rec(
@@ -119,7 +368,17 @@ fn draw_cell(
                 textures.push(&assets.map.wall.corner_inside);
             }
         }
-        Cell::Gap => {}
+        Cell::Gap => {
+            d.draw_rectangle_rec(
+                Rectangle {
+                    x: pos.x,
+                    y: pos.y,
+                    width: size,
+                    height: size,
+                },
+                Color::new(10, 10, 10, 255),
+            );
+        }
     }
     for texture in textures {
         d.draw_texture_pro(
@@ -0,0 +1,80 @@
+use std::fmt;
+
+use robot_coding_game::{Brain, Instruction, InstructionType};
+
+/// Undo and redo stacks of one robot's brain. Each step is a snapshot of the instructions from
+/// before an edit, where a whole mouse drag counts as one edit.
+#[derive(Default)]
+pub struct BrainHistory {
+    undo: Vec<Vec<Instruction>>,
+    redo: Vec<Vec<Instruction>>,
+    /// The instructions from before the edit that is still in progress.
+    pending: Option<Vec<Instruction>>,
+}
+
+/// Why a snapshot couldn't be put back into the brain. The snapshot stays on its stack.
+pub enum Refused {
+    /// The budget no longer allows this many instructions of the type.
+    OverBudget(InstructionType),
+    /// The brain has a different size than when the snapshot was taken.
+    Resized,
+}
+
+impl fmt::Display for Refused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Refused::OverBudget(instruction_type) => {
+                write!(f, "not enough {instruction_type:?} instructions left")
+            }
+            Refused::Resized => write!(f, "the brain changed size"),
+        }
+    }
+}
+
+impl BrainHistory {
+    /// Starts an edit. Changes made to `brain` until `end` become a single undo step.
+    pub fn begin(&mut self, brain: &Brain) {
+        if self.pending.is_none() {
+            self.pending = Some(brain.instructions.clone());
+        }
+    }
+    /// Finishes the edit started by `begin`, recording it if anything changed.
+    pub fn end(&mut self, brain: &Brain) {
+        if let Some(before) = self.pending.take() {
+            if before != brain.instructions {
+                self.undo.push(before);
+                self.redo.clear();
+            }
+        }
+    }
+    /// Goes back one step, if there is one. Refuses to restore instructions the brain's budget
+    /// no longer allows.
+    pub fn undo(&mut self, brain: &mut Brain) -> Result<(), Refused> {
+        self.end(brain);
+        restore(&mut self.undo, &mut self.redo, brain)
+    }
+    pub fn redo(&mut self, brain: &mut Brain) -> Result<(), Refused> {
+        self.end(brain);
+        restore(&mut self.redo, &mut self.undo, brain)
+    }
+}
+
+/// Puts the newest snapshot in `from` into `brain`, saving what it replaced in `to`.
+fn restore(
+    from: &mut Vec<Vec<Instruction>>,
+    to: &mut Vec<Vec<Instruction>>,
+    brain: &mut Brain,
+) -> Result<(), Refused> {
+    let Some(instructions) = from.last() else {
+        return Ok(());
+    };
+    if instructions.len() != brain.instructions.len() {
+        return Err(Refused::Resized);
+    }
+    if let Some(instruction_type) = brain.over_budget(instructions) {
+        return Err(Refused::OverBudget(instruction_type));
+    }
+    let instructions = from.pop().unwrap();
+    to.push(std::mem::replace(&mut brain.instructions, instructions));
+    Ok(())
+}
@@ -3,25 +3,130 @@ use std::{fs::File, io::BufReader};
 use raylib::prelude::*;
 use rodio::{Decoder, OutputStreamHandle, Source};
 
-use crate::{Assets, BrainEdit, InstructionType, Map, Rotation, World};
+use robot_coding_game::{Brain, InstructionType, Level, Region, Rotation, Trace, World};
+
+use crate::{
+    board_cell_at, brain_cell_at, brain_panel_rect, Assets, BoardView, BrainDrag, BrainEdit,
+    Notice, Simulation, SPEEDS,
+};
 
 pub fn inputs(
     rl: &mut RaylibHandle,
     world: &mut World,
     assets: &Assets,
     mouse_pos: Vector2,
+    board: &BoardView,
     brain_edit: &mut BrainEdit,
     sound_handle: &OutputStreamHandle,
-    stepping: bool,
-) -> bool {
-    let mut stepping = stepping;
+    simulation: &mut Simulation,
+    level: &Level,
+    level_name: &str,
+    notice: &mut Notice,
+) {
     if rl.is_key_pressed(KeyboardKey::KEY_SPACE) {
-        stepping = !stepping;
+        simulation.stepping = !simulation.stepping;
+        if simulation.stepping {
+            start_run(world, simulation, level);
+        }
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_PERIOD) {
+        start_run(world, simulation, level);
+        simulation.stepping = false;
+        simulation.time_since_last_step = 0.0;
+        world.step();
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_EQUAL) && simulation.speed + 1 < SPEEDS.len() {
+        simulation.speed += 1;
+        simulation.time_since_last_step = 0.0;
     }
-    if brain_edit.id.is_some() {
+    if rl.is_key_pressed(KeyboardKey::KEY_MINUS) && simulation.speed > 0 {
+        simulation.speed -= 1;
+        simulation.time_since_last_step = 0.0;
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_R) {
+        if let Some(initial_world) = simulation.initial_world.take() {
+            world.reset_to(&initial_world);
+        }
+        simulation.stepping = false;
+        simulation.time_since_last_step = 0.0;
+    }
+    let wheel_on_selection = over_selection(world, assets, mouse_pos, brain_edit);
+    if let Some(id) = brain_edit.id {
+        let history = brain_edit.histories.entry(id).or_default();
+        if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+            history.begin(&world.robots[id].brain);
+        }
+        selection(
+            rl,
+            &mut world.robots[id].brain,
+            assets,
+            mouse_pos,
+            brain_edit,
+            notice,
+        );
         brain(rl, world, assets, mouse_pos, sound_handle, brain_edit);
+        let history = brain_edit.histories.entry(id).or_default();
+        let brain = &mut world.robots[id].brain;
+        if rl.is_mouse_button_released(MouseButton::MOUSE_BUTTON_LEFT) {
+            history.end(brain);
+        }
+        let ctrl = rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL)
+            || rl.is_key_down(KeyboardKey::KEY_RIGHT_CONTROL);
+        let shift = rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT)
+            || rl.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT);
+        if ctrl && !shift && rl.is_key_pressed(KeyboardKey::KEY_Z) {
+            if let Err(refused) = history.undo(brain) {
+                notice.error(format!("can't undo: {refused}"));
+            }
+        }
+        if ctrl
+            && (rl.is_key_pressed(KeyboardKey::KEY_Y)
+                || (shift && rl.is_key_pressed(KeyboardKey::KEY_Z)))
+        {
+            if let Err(refused) = history.redo(brain) {
+                notice.error(format!("can't redo: {refused}"));
+            }
+        }
+        let path = format!("solutions/{level_name}/robot_{id}.ron");
+        if ctrl && rl.is_key_pressed(KeyboardKey::KEY_S) {
+            match brain.save_program(&path) {
+                Ok(()) => notice.info(format!("Saved program to {path}")),
+                Err(error) => notice.error(error),
+            }
+        }
+        if ctrl && rl.is_key_pressed(KeyboardKey::KEY_L) {
+            history.begin(brain);
+            match brain.load_program(&path) {
+                Ok(()) => notice.info(format!("Loaded program from {path}")),
+                Err(error) => notice.error(error),
+            }
+            history.end(brain);
+        }
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
+        if brain_edit.selection.is_some() {
+            brain_edit.selection = None;
+        } else {
+            brain_edit.id = None;
+        }
     }
-    if rl.get_mouse_wheel_move() > 0.0 {
+    if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+        select_robot(world, assets, mouse_pos, board, brain_edit);
+    }
+    let channel_keys = [
+        KeyboardKey::KEY_ONE,
+        KeyboardKey::KEY_TWO,
+        KeyboardKey::KEY_THREE,
+        KeyboardKey::KEY_FOUR,
+    ];
+    for (channel, key) in channel_keys.into_iter().enumerate() {
+        if rl.is_key_pressed(key) {
+            brain_edit.selected_instruction.channel = channel;
+        }
+    }
+    if wheel_on_selection {
+        // The wheel turns the selection instead, see `selection`
+    } else if rl.get_mouse_wheel_move() > 0.0 {
         brain_edit.selected_instruction.rotation = match brain_edit.selected_instruction.rotation {
             Rotation::Up => Rotation::Right,
             Rotation::Right => Rotation::Down,
@@ -36,8 +141,220 @@ pub fn inputs(
             Rotation::Left => Rotation::Down,
         }
     }
+}
+
+/// Remembers the world as it is before the first tick of a run so it can be reset later, and
+/// starts recording a new trace of `level`.
+fn start_run(world: &mut World, simulation: &mut Simulation, level: &Level) {
+    if simulation.initial_world.is_none() {
+        // Dropped first so the snapshot doesn't carry a copy of the previous run's trace
+        world.trace = None;
+        simulation.initial_world = Some(world.clone());
+        world.trace = Some(Trace::new(level.clone(), world));
+    }
+}
+
+/// Opens the brain of the robot under the mouse, or closes the editor when clicking anywhere
+/// else outside of it.
+fn select_robot(
+    world: &World,
+    assets: &Assets,
+    mouse_pos: Vector2,
+    board: &BoardView,
+    brain_edit: &mut BrainEdit,
+) {
+    if let Some(id) = brain_edit.id {
+        let panel = brain_panel_rect(&world.robots[id].brain, brain_edit, assets);
+        if panel.check_collision_point_rec(mouse_pos) {
+            return;
+        }
+    }
+    let cell = board_cell_at(world, board.pos, board.width, board.scale, mouse_pos);
+    let id = world.robots.iter().position(|robot| robot.pos == cell);
+    if id != brain_edit.id {
+        brain_edit.selection = None;
+        brain_edit.drag = BrainDrag::None;
+    }
+    brain_edit.id = id;
+}
+
+/// Whether the mouse is over the selected rectangle of the open brain.
+fn over_selection(
+    world: &World,
+    assets: &Assets,
+    mouse_pos: Vector2,
+    brain_edit: &BrainEdit,
+) -> bool {
+    match (brain_edit.id, brain_edit.selection) {
+        (Some(id), Some(selection)) => {
+            let cell = brain_cell_at(&world.robots[id].brain, brain_edit, assets, mouse_pos);
+            contains(selection, cell)
+        }
+        _ => false,
+    }
+}
+
+fn contains(selection: ((i32, i32), (i32, i32)), cell: (i32, i32)) -> bool {
+    let ((x0, y0), (x1, y1)) = selection;
+    (x0.min(x1)..=x0.max(x1)).contains(&cell.0) && (y0.min(y1)..=y0.max(y1)).contains(&cell.1)
+}
+
+/// Rectangle selection in the open brain. Shift-dragging selects, dragging the selection moves
+/// it, the mouse wheel over it turns it, and Ctrl+C, Ctrl+X, Ctrl+V and Delete copy, cut, paste
+/// and clear it. The clipboard is kept when switching robots so regions can be pasted into
+/// another brain.
+fn selection(
+    rl: &mut RaylibHandle,
+    brain: &mut Brain,
+    assets: &Assets,
+    mouse_pos: Vector2,
+    brain_edit: &mut BrainEdit,
+    notice: &mut Notice,
+) {
+    let cell = brain_cell_at(brain, brain_edit, assets, mouse_pos);
+    let clamped = (
+        cell.0.clamp(0, brain.width as i32 - 1),
+        cell.1.clamp(0, brain.height as i32 - 1),
+    );
+    let ctrl = rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL)
+        || rl.is_key_down(KeyboardKey::KEY_RIGHT_CONTROL);
+    let shift =
+        rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) || rl.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT);
+
+    if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) && brain.in_bounds(cell) {
+        if shift {
+            brain_edit.drag = BrainDrag::Selecting;
+            brain_edit.selection = Some((cell, cell));
+        } else if brain_edit
+            .selection
+            .is_some_and(|selection| contains(selection, cell))
+        {
+            brain_edit.drag = BrainDrag::Moving { from: cell };
+        } else {
+            brain_edit.selection = None;
+        }
+    }
+    match brain_edit.drag {
+        BrainDrag::None => {}
+        BrainDrag::Selecting => {
+            if let Some(selection) = &mut brain_edit.selection {
+                selection.1 = clamped;
+            }
+        }
+        BrainDrag::Moving { from } => {
+            if rl.is_mouse_button_released(MouseButton::MOUSE_BUTTON_LEFT) {
+                if let Some(selection) = brain_edit.selection {
+                    let offset = move_offset(brain, brain_edit, assets, mouse_pos, from);
+                    let region = brain.region(selection.0, selection.1);
+                    brain_edit.selection =
+                        Some(replace_selection(brain, selection, &region, offset, notice));
+                }
+            }
+        }
+    }
+    if rl.is_mouse_button_released(MouseButton::MOUSE_BUTTON_LEFT) {
+        brain_edit.drag = BrainDrag::None;
+    }
 
-    stepping
+    let id = brain_edit.id.unwrap();
+    let history = brain_edit.histories.entry(id).or_default();
+    history.begin(brain);
+    if let Some(selection) = brain_edit.selection {
+        if ctrl && (rl.is_key_pressed(KeyboardKey::KEY_C) || rl.is_key_pressed(KeyboardKey::KEY_X))
+        {
+            brain_edit.clipboard = Some(brain.region(selection.0, selection.1));
+        }
+        if (ctrl && rl.is_key_pressed(KeyboardKey::KEY_X))
+            || rl.is_key_pressed(KeyboardKey::KEY_DELETE)
+            || rl.is_key_pressed(KeyboardKey::KEY_BACKSPACE)
+        {
+            brain.clear_region(selection.0, selection.1);
+        }
+        if contains(selection, cell) && rl.get_mouse_wheel_move() != 0.0 {
+            let region = brain.region(selection.0, selection.1);
+            let region = match rl.get_mouse_wheel_move() > 0.0 {
+                true => region.rotate_clockwise(),
+                false => region.rotate_counter_clockwise(),
+            };
+            brain_edit.selection =
+                Some(replace_selection(brain, selection, &region, (0, 0), notice));
+        }
+    }
+    if ctrl && rl.is_key_pressed(KeyboardKey::KEY_V) && brain.in_bounds(cell) {
+        if let Some(region) = &brain_edit.clipboard {
+            match brain.paste(region, cell) {
+                Ok(()) => {
+                    brain_edit.selection = Some((
+                        cell,
+                        (
+                            (cell.0 + region.width as i32 - 1).min(brain.width as i32 - 1),
+                            (cell.1 + region.height as i32 - 1).min(brain.height as i32 - 1),
+                        ),
+                    ));
+                }
+                Err(instruction_type) => {
+                    notice.error(format!(
+                        "not enough {instruction_type:?} instructions left to paste"
+                    ));
+                }
+            }
+        }
+    }
+    // Mouse edits are recorded when the button is released instead
+    if !rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
+        history.end(brain);
+    }
+}
+
+/// How far a selection grabbed at cell `from` is moved with the mouse at `mouse_pos`. The
+/// mouse is kept inside the brain, so the preview and the drop agree on where the selection
+/// goes.
+pub fn move_offset(
+    brain: &Brain,
+    brain_edit: &BrainEdit,
+    assets: &Assets,
+    mouse_pos: Vector2,
+    from: (i32, i32),
+) -> (i32, i32) {
+    let cell = brain_cell_at(brain, brain_edit, assets, mouse_pos);
+    (
+        cell.0.clamp(0, brain.width as i32 - 1) - from.0,
+        cell.1.clamp(0, brain.height as i32 - 1) - from.1,
+    )
+}
+
+/// Clears the selected instructions and puts `region` in their place, moved by `offset`.
+/// Returns the new selection, or leaves the brain and selection as they were if `region`
+/// would not fit inside the brain or the budget.
+fn replace_selection(
+    brain: &mut Brain,
+    selection: ((i32, i32), (i32, i32)),
+    region: &Region,
+    offset: (i32, i32),
+    notice: &mut Notice,
+) -> ((i32, i32), (i32, i32)) {
+    let min = (
+        selection.0 .0.min(selection.1 .0) + offset.0,
+        selection.0 .1.min(selection.1 .1) + offset.1,
+    );
+    let max = (
+        min.0 + region.width as i32 - 1,
+        min.1 + region.height as i32 - 1,
+    );
+    if !brain.in_bounds(min) || !brain.in_bounds(max) {
+        notice.error("the selection does not fit inside the brain there");
+        return selection;
+    }
+    let instructions = brain.instructions.clone();
+    brain.clear_region(selection.0, selection.1);
+    if let Err(instruction_type) = brain.paste(region, min) {
+        notice.error(format!(
+            "not enough {instruction_type:?} instructions left to move the selection"
+        ));
+        brain.instructions = instructions;
+        return selection;
+    }
+    (min, max)
 }
 
 fn brain(
@@ -63,6 +380,8 @@ fn brain(
         let index = mouse_brain_pos.x as usize + mouse_brain_pos.y as usize * brain.width as usize;
         let old_instruction = brain.instructions[index];
         if rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT)
+            && brain_edit.drag == BrainDrag::None
+            && brain_edit.selection.is_none()
             && (instruction_count != 0
                 || brain_edit.selected_instruction.instruction_type
                     == old_instruction.instruction_type)
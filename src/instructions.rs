@@ -1,134 +1,325 @@
-use crate::{Cell, InstructionType, Map, Rotation, World};
+use crate::{
+    Brain, Cell, InstructionType, LevelStatus, Map, Object, ObjectKind, Robot, Rotation, Signals,
+    WinCondition, World, CHANNELS, MAX_COUNTER,
+};
 
-pub fn update_robots(
-    world: &mut World,
-    read_next: bool,
-    time_since_last_step: f32,
-    update_dt: f32,
-) -> (bool, f32) {
-    let mut read_next = read_next;
+/// Advances `world` by as many whole ticks as fit into `time_since_last_step` and returns the
+/// time left over.
+pub fn update_robots(world: &mut World, time_since_last_step: f32, update_dt: f32) -> f32 {
     let mut time_since_last_step = time_since_last_step;
     while time_since_last_step > update_dt {
-        for robot in &mut world.robots {
-            let brain = &mut robot.brain;
-            let instruction = brain.get_instruction(brain.reader.pos);
-            if read_next {
-                match instruction.instruction_type {
-                    InstructionType::Move => {
-                        let pos = match robot.rotation {
-                            Rotation::Up => (robot.pos.0, robot.pos.1 + 1),
-                            Rotation::Right => (robot.pos.0 + 1, robot.pos.1),
-                            Rotation::Down => (robot.pos.0, robot.pos.1 - 1),
-                            Rotation::Left => (robot.pos.0 - 1, robot.pos.1),
-                        };
-                        let cell = world.map.get_cell_type(pos.0, pos.1);
-                        match cell {
-                            Cell::Empty => robot.pos = pos,
-                            Cell::Wall => {}
-                            Cell::Gap => {},
-                        }
+        world.step();
+        time_since_last_step -= update_dt;
+    }
+    time_since_last_step
+}
+
+impl World {
+    /// Advances the simulation by exactly one tick. Does nothing once the level is over.
+    pub fn step(&mut self) {
+        if self.status != LevelStatus::Running {
+            return;
+        }
+        self.ticks += 1;
+        for object in &mut self.objects {
+            object.last_pos = object.pos;
+        }
+        self.signals.previous = self.signals.current;
+        self.signals.current = [false; CHANNELS];
+        let traced = self.trace.as_mut().map(|trace| {
+            let readers: Vec<_> = self
+                .robots
+                .iter()
+                .map(|robot| (robot.brain.reader, robot.brain.pending_instruction()))
+                .collect();
+            (trace.edits(&self.robots), readers)
+        });
+        let intents: Vec<Option<(i32, i32)>> = self
+            .robots
+            .iter_mut()
+            .map(|robot| robot.step(&mut self.map, &self.objects, &mut self.signals))
+            .collect();
+        let moving = self.resolve_moves(&intents);
+        self.apply_moves(&intents, &moving);
+        if let (Some(trace), Some((edits, readers))) = (&mut self.trace, traced) {
+            trace.record(edits, &readers, &self.robots, &intents, &moving);
+        }
+        self.update_doors();
+        if self
+            .robots
+            .iter()
+            .any(|robot| self.map.get_cell_type(robot.pos.0, robot.pos.1) == Cell::Gap)
+        {
+            self.status = LevelStatus::Failed;
+        } else if self.win_condition.is_met(self) {
+            self.status = LevelStatus::Complete;
+        } else {
+            let mut loop_check = std::mem::take(&mut self.loop_check);
+            if loop_check.repeats(self) {
+                self.status = LevelStatus::Stuck(self.stuck());
+            }
+            self.loop_check = loop_check;
+        }
+    }
+    /// Puts robots, readers and the map back to how they were in `initial`, keeping the
+    /// instructions currently placed in each brain.
+    pub fn reset_to(&mut self, initial: &World) {
+        for (robot, initial_robot) in self.robots.iter_mut().zip(&initial.robots) {
+            let instructions = std::mem::take(&mut robot.brain.instructions);
+            *robot = initial_robot.clone();
+            robot.brain.instructions = instructions;
+        }
+        self.map = initial.map.clone();
+        self.objects = initial.objects.clone();
+        self.keys = initial.keys;
+        self.signals = initial.signals;
+        self.status = initial.status;
+        self.ticks = initial.ticks;
+        self.loop_check = initial.loop_check.clone();
+    }
+    /// Whether a plate on `channel` has a robot or crate standing on it.
+    pub fn plate_pressed(&self, channel: usize) -> bool {
+        self.objects
+            .iter()
+            .any(|plate| plate.kind == ObjectKind::Plate { channel } && self.occupied(plate.pos))
+    }
+    /// Whether a robot or crate stands at `pos`.
+    fn occupied(&self, pos: (i32, i32)) -> bool {
+        self.robots.iter().any(|robot| robot.pos == pos)
+            || self
+                .objects
+                .iter()
+                .any(|object| object.kind == ObjectKind::Crate && object.pos == pos)
+    }
+    /// Opens and closes doors at the end of a tick, once everything has moved.
+    fn update_doors(&mut self) {
+        for i in 0..self.objects.len() {
+            if let ObjectKind::Door { channel, open } = self.objects[i].kind {
+                let open = self.keys[channel]
+                    || self.plate_pressed(channel)
+                    || (open && self.occupied(self.objects[i].pos));
+                self.objects[i].kind = ObjectKind::Door { channel, open };
+            }
+        }
+    }
+}
+
+impl WinCondition {
+    pub fn is_met(&self, world: &World) -> bool {
+        match *self {
+            WinCondition::AllRobotsOnGoals => {
+                !world.robots.is_empty()
+                    && world.robots.iter().all(|robot| {
+                        world.map.get_cell_type(robot.pos.0, robot.pos.1) == Cell::Goal
+                    })
+            }
+            WinCondition::RobotAt { robot, pos } => world
+                .robots
+                .get(robot)
+                .is_some_and(|robot| robot.pos == pos),
+            WinCondition::ItemsOnGoals => {
+                !world.map.items.is_empty()
+                    && world.robots.iter().all(|robot| robot.carrying.is_none())
+                    && world
+                        .map
+                        .items
+                        .iter()
+                        .all(|(pos, _)| world.map.get_cell_type(pos.0, pos.1) == Cell::Goal)
+            }
+        }
+    }
+}
+
+impl Robot {
+    /// Runs one tick of this robot's brain and returns the cell it wants to move to. Sensors see
+    /// the world as it was at the start of the tick, and the move itself is made by
+    /// `World::step` once every robot has decided, see `World::resolve_moves`. `Grab` and
+    /// `Drop` take effect straight away, as only this robot can be standing on its cell.
+    pub fn step(
+        &mut self,
+        map: &mut Map,
+        objects: &[Object],
+        signals: &mut Signals,
+    ) -> Option<(i32, i32)> {
+        self.last_pos = self.pos;
+        self.last_rotation = self.rotation;
+        let brain = &mut self.brain;
+        if brain.reader.halted {
+            return None;
+        }
+        let mut intent = None;
+        let mut listening = false;
+        brain.reader.cycles += 1;
+        let instruction = brain.get_instruction(brain.reader.pos);
+        if brain.reader.waiting > 0 {
+            brain.reader.waiting -= 1;
+        } else if brain.reader.read_next {
+            match instruction.instruction_type {
+                InstructionType::Move => {
+                    let pos = ahead(self.pos, self.rotation);
+                    intent = Some(pos);
+                }
+                InstructionType::Back => {
+                    let pos = match self.rotation {
+                        Rotation::Up => (self.pos.0, self.pos.1 - 1),
+                        Rotation::Right => (self.pos.0 - 1, self.pos.1),
+                        Rotation::Down => (self.pos.0, self.pos.1 + 1),
+                        Rotation::Left => (self.pos.0 + 1, self.pos.1),
+                    };
+                    intent = Some(pos);
+                }
+                InstructionType::Direction => {
+                    brain.reader.rotation = instruction.rotation;
+                }
+                InstructionType::WallAhead => {
+                    let pos = ahead(self.pos, self.rotation);
+                    let closed_door = objects.iter().any(|object| {
+                        object.pos == pos
+                            && matches!(object.kind, ObjectKind::Door { open: false, .. })
+                    });
+                    if map.get_cell_type(pos.0, pos.1) == Cell::Wall || closed_door {
+                        brain.reader.rotation = instruction.rotation;
                     }
-                    InstructionType::Back => {
-                        let pos = match robot.rotation {
-                            Rotation::Up => (robot.pos.0, robot.pos.1 - 1),
-                            Rotation::Right => (robot.pos.0 - 1, robot.pos.1),
-                            Rotation::Down => (robot.pos.0, robot.pos.1 + 1),
-                            Rotation::Left => (robot.pos.0 + 1, robot.pos.1),
-                        };
-                        let cell = world.map.get_cell_type(pos.0, pos.1);
-                        match cell {
-                            Cell::Empty => robot.pos = pos,
-                            Cell::Wall => {}
-                            Cell::Gap => {},
-                        }
+                }
+                InstructionType::GapAhead => {
+                    let pos = ahead(self.pos, self.rotation);
+                    if map.get_cell_type(pos.0, pos.1) == Cell::Gap {
+                        brain.reader.rotation = instruction.rotation;
                     }
-                    InstructionType::Direction => {
+                }
+                InstructionType::OnGoal => {
+                    if map.get_cell_type(self.pos.0, self.pos.1) == Cell::Goal {
                         brain.reader.rotation = instruction.rotation;
                     }
-                    InstructionType::None => {}
-                    InstructionType::RotateLeft => {
-                        robot.rotation = match robot.rotation {
-                            Rotation::Up => Rotation::Left,
-                            Rotation::Right => Rotation::Up,
-                            Rotation::Down => Rotation::Right,
-                            Rotation::Left => Rotation::Down,
-                        }
+                }
+                InstructionType::None => {}
+                InstructionType::RotateLeft => {
+                    self.rotation = match self.rotation {
+                        Rotation::Up => Rotation::Left,
+                        Rotation::Right => Rotation::Up,
+                        Rotation::Down => Rotation::Right,
+                        Rotation::Left => Rotation::Down,
                     }
-                    InstructionType::RotateRight => {
-                        robot.rotation = match robot.rotation {
-                            Rotation::Up => Rotation::Right,
-                            Rotation::Right => Rotation::Down,
-                            Rotation::Down => Rotation::Left,
-                            Rotation::Left => Rotation::Up,
-                        }
+                }
+                InstructionType::RotateRight => {
+                    self.rotation = match self.rotation {
+                        Rotation::Up => Rotation::Right,
+                        Rotation::Right => Rotation::Down,
+                        Rotation::Down => Rotation::Left,
+                        Rotation::Left => Rotation::Up,
                     }
-                    InstructionType::Blank => {}
                 }
-            }
-            match brain.reader.rotation {
-                Rotation::Up => {
-                    if brain.in_bounds((brain.reader.pos.0, brain.reader.pos.1 + 1))
-                        && !matches!(
-                            brain
-                                .get_instruction((brain.reader.pos.0, brain.reader.pos.1 + 1))
-                                .instruction_type,
-                            InstructionType::None
-                        )
-                    {
-                        brain.reader.pos.1 += 1;
-                        read_next = true;
-                    } else {
-                        read_next = false;
+                InstructionType::Increment => {
+                    let counter = &mut brain.counters[instruction.channel];
+                    *counter = (*counter + 1).min(MAX_COUNTER);
+                }
+                InstructionType::Decrement => {
+                    let counter = &mut brain.counters[instruction.channel];
+                    *counter = counter.saturating_sub(1);
+                }
+                InstructionType::BranchIfZero => {
+                    if brain.counters[instruction.channel] == 0 {
+                        brain.reader.rotation = instruction.rotation;
                     }
                 }
-                Rotation::Right => {
-                    if brain.in_bounds((brain.reader.pos.0 + 1, brain.reader.pos.1))
-                        && !matches!(
-                            brain
-                                .get_instruction((brain.reader.pos.0 + 1, brain.reader.pos.1))
-                                .instruction_type,
-                            InstructionType::None
-                        )
-                    {
-                        brain.reader.pos.0 += 1;
-                        read_next = true;
-                    } else {
-                        read_next = false;
+                InstructionType::Jump => {
+                    if let Some(target) = brain.jump_target(brain.reader.pos, instruction.channel) {
+                        brain.reader.pos = target;
                     }
                 }
-                Rotation::Down => {
-                    if brain.in_bounds((brain.reader.pos.0, brain.reader.pos.1 - 1))
-                        && !matches!(
-                            brain
-                                .get_instruction((brain.reader.pos.0, brain.reader.pos.1 - 1))
-                                .instruction_type,
-                            InstructionType::None
-                        )
-                    {
-                        brain.reader.pos.1 -= 1;
-                        read_next = true;
-                    } else {
-                        read_next = false;
+                InstructionType::Wait => {
+                    brain.reader.waiting = instruction.wait_ticks() - 1;
+                }
+                InstructionType::Halt => {
+                    brain.reader.halted = true;
+                }
+                InstructionType::Signal => {
+                    signals.current[instruction.channel] = true;
+                }
+                InstructionType::WaitForSignal => {
+                    listening = !signals.previous[instruction.channel];
+                }
+                InstructionType::Grab => {
+                    if self.carrying.is_none() {
+                        self.carrying = map.take_item(self.pos);
                     }
                 }
-                Rotation::Left => {
-                    if brain.in_bounds((brain.reader.pos.0 - 1, brain.reader.pos.1))
-                        && !matches!(
-                            brain
-                                .get_instruction((brain.reader.pos.0 - 1, brain.reader.pos.1))
-                                .instruction_type,
-                            InstructionType::None
-                        )
-                    {
-                        brain.reader.pos.0 -= 1;
-                        read_next = true;
-                    } else {
-                        read_next = false;
+                InstructionType::Drop => {
+                    if map.get_item(self.pos).is_none() {
+                        if let Some(item) = self.carrying.take() {
+                            map.items.push((self.pos, item));
+                        }
                     }
                 }
+                InstructionType::Blank => {}
             }
         }
-        time_since_last_step -= update_dt;
+        if brain.reader.waiting > 0 || brain.reader.halted || listening {
+            return intent;
+        }
+        let next = brain.next_reader_pos();
+        if brain.in_bounds(next)
+            && brain.get_instruction(next).instruction_type != InstructionType::None
+        {
+            brain.reader.pos = next;
+            brain.reader.read_next = true;
+        } else {
+            brain.reader.read_next = false;
+        }
+        intent
+    }
+}
+
+impl Brain {
+    /// The instruction the reader runs on the next tick, if it runs one.
+    pub fn pending_instruction(&self) -> Option<InstructionType> {
+        let reader = &self.reader;
+        if reader.halted || reader.waiting > 0 || !reader.read_next {
+            return None;
+        }
+        Some(self.get_instruction(reader.pos).instruction_type)
+            .filter(|&instruction_type| instruction_type != InstructionType::None)
+    }
+    /// The cell the reader moves to after this one, wrapping around the edges of the grid when
+    /// `wrap` is set.
+    pub fn next_reader_pos(&self) -> (i32, i32) {
+        let pos = ahead(self.reader.pos, self.reader.rotation);
+        if self.wrap {
+            (
+                pos.0.rem_euclid(self.width as i32),
+                pos.1.rem_euclid(self.height as i32),
+            )
+        } else {
+            pos
+        }
+    }
+    /// The next `Jump` on `channel` after `pos` in reading order, looping back to the start of
+    /// the grid. A lone `Jump` has no target.
+    pub fn jump_target(&self, pos: (i32, i32), channel: usize) -> Option<(i32, i32)> {
+        let start = (pos.0 + pos.1 * self.width as i32) as usize;
+        let len = self.instructions.len();
+        (1..len)
+            .map(|offset| (start + offset) % len)
+            .find(|&index| {
+                let instruction = &self.instructions[index];
+                instruction.instruction_type == InstructionType::Jump
+                    && instruction.channel == channel
+            })
+            .map(|index| {
+                (
+                    (index % self.width as usize) as i32,
+                    (index / self.width as usize) as i32,
+                )
+            })
+    }
+}
+
+/// The cell directly in front of `pos` when facing `rotation`.
+fn ahead(pos: (i32, i32), rotation: Rotation) -> (i32, i32) {
+    match rotation {
+        Rotation::Up => (pos.0, pos.1 + 1),
+        Rotation::Right => (pos.0 + 1, pos.1),
+        Rotation::Down => (pos.0, pos.1 - 1),
+        Rotation::Left => (pos.0 - 1, pos.1),
     }
-    (read_next, time_since_last_step)
 }
@@ -0,0 +1,315 @@
+use std::{cmp::Ordering, fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ron_file, Cell, FileError, InstructionType, LevelStatus, LoopCheck, Map, Object, ObjectKind,
+    Robot, Rotation, Score, Signals, WinCondition, World, CHANNELS,
+};
+
+/// A puzzle as authored by a designer. Levels are stored as RON files, e.g.
+///
+/// ```ron
+/// (
+///     map: (
+///         width: 3,
+///         height: 2,
+///         // Row y = 0 is the bottom of the board.
+///         cells: [
+///             Empty, Wall, Empty,
+///             Empty, Empty, Empty,
+///         ],
+///         // Optional, defaults to no items.
+///         items: [((2, 0), Gem)],
+///     ),
+///     robots: [
+///         (
+///             pos: (0, 0),
+///             rotation: Up,
+///             brain_width: 5,
+///             brain_height: 5,
+///             total_instructions: [(5, Move), (5, Direction)],
+///         ),
+///     ],
+///     // Optional, defaults to no objects.
+///     objects: [
+///         (kind: Crate, pos: (0, 1)),
+///         (kind: Plate(channel: 0), pos: (2, 0)),
+///         (kind: Door(channel: 0), pos: (1, 1)),
+///     ],
+///     // Optional, defaults to every robot standing on a `Goal` cell.
+///     win_condition: RobotAt(robot: 0, pos: (2, 1)),
+///     // Optional, defaults to readers stopping at the edge of the brain.
+///     brain_wrap: true,
+///     // Optional, scores of earlier solutions the player's score is compared against.
+///     reference_scores: [(ticks: 12, instructions: 4, area: 6)],
+/// )
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Level {
+    pub map: Map,
+    pub robots: Vec<RobotSpawn>,
+    #[serde(default)]
+    pub objects: Vec<ObjectSpawn>,
+    #[serde(default)]
+    pub win_condition: WinCondition,
+    /// Whether brain readers wrap around the edges of the grid.
+    #[serde(default)]
+    pub brain_wrap: bool,
+    #[serde(default)]
+    pub reference_scores: Vec<Score>,
+}
+
+/// Where a robot starts and what its brain is allowed to hold.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RobotSpawn {
+    pub pos: (i32, i32),
+    pub rotation: Rotation,
+    pub brain_width: u32,
+    pub brain_height: u32,
+    pub total_instructions: Vec<(usize, InstructionType)>,
+}
+
+/// A crate, key, door or plate and the cell it starts on.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ObjectSpawn {
+    pub kind: ObjectKind,
+    pub pos: (i32, i32),
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    File(FileError),
+    Invalid(String),
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::File(error) => write!(f, "level: {error}"),
+            LevelError::Invalid(reason) => write!(f, "invalid level: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LevelError::File(error) => Some(error),
+            LevelError::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<FileError> for LevelError {
+    fn from(error: FileError) -> Self {
+        LevelError::File(error)
+    }
+}
+
+impl Level {
+    pub fn load(path: impl AsRef<Path>) -> Result<Level, LevelError> {
+        let level: Level = ron_file::load(path)?;
+        level.validate()?;
+        Ok(level)
+    }
+    pub fn parse(source: &str) -> Result<Level, LevelError> {
+        let level: Level = ron_file::parse(source)?;
+        level.validate()?;
+        Ok(level)
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), LevelError> {
+        self.validate()?;
+        Ok(ron_file::save(self, path)?)
+    }
+    /// Changes the size of the map, keeping the cells that still fit and removing robots,
+    /// objects and items that end up outside of it.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.map.resize(width, height);
+        let inside = |pos: (i32, i32)| (pos.0 as usize) < width && (pos.1 as usize) < height;
+        self.robots.retain(|robot| inside(robot.pos));
+        self.objects.retain(|object| inside(object.pos));
+    }
+    /// Checks that the level can be played, e.g. that every robot starts on the floor.
+    pub fn validate(&self) -> Result<(), LevelError> {
+        if self.map.cells.len() != self.map.width * self.map.height {
+            return Err(LevelError::Invalid(format!(
+                "map is {}x{} but has {} cells",
+                self.map.width,
+                self.map.height,
+                self.map.cells.len()
+            )));
+        }
+        for (i, robot) in self.robots.iter().enumerate() {
+            if robot.brain_width == 0 || robot.brain_height == 0 {
+                return Err(LevelError::Invalid(format!("robot {i} has an empty brain")));
+            }
+            let cell = self.map.get_cell_type(robot.pos.0, robot.pos.1);
+            if !matches!(cell, Cell::Empty | Cell::Goal) {
+                return Err(LevelError::Invalid(format!(
+                    "robot {i} starts at {:?} which is not a floor cell",
+                    robot.pos
+                )));
+            }
+            if self.robots[..i].iter().any(|other| other.pos == robot.pos) {
+                return Err(LevelError::Invalid(format!(
+                    "robot {i} shares {:?} with another robot",
+                    robot.pos
+                )));
+            }
+        }
+        for (i, (pos, _)) in self.map.items.iter().enumerate() {
+            if !matches!(
+                self.map.get_cell_type(pos.0, pos.1),
+                Cell::Empty | Cell::Goal
+            ) {
+                return Err(LevelError::Invalid(format!(
+                    "item {i} is at {pos:?} which is not a floor cell"
+                )));
+            }
+            if self.map.items[..i].iter().any(|(other, _)| other == pos) {
+                return Err(LevelError::Invalid(format!(
+                    "item {i} shares {pos:?} with another item"
+                )));
+            }
+        }
+        for (i, object) in self.objects.iter().enumerate() {
+            let channel = match object.kind {
+                ObjectKind::Crate => 0,
+                ObjectKind::Key { channel }
+                | ObjectKind::Door { channel, .. }
+                | ObjectKind::Plate { channel } => channel,
+            };
+            if channel >= CHANNELS {
+                return Err(LevelError::Invalid(format!(
+                    "object {i} uses channel {channel} but there are only {CHANNELS}"
+                )));
+            }
+            let cell = self.map.get_cell_type(object.pos.0, object.pos.1);
+            if !matches!(cell, Cell::Empty | Cell::Goal) {
+                return Err(LevelError::Invalid(format!(
+                    "object {i} is at {:?} which is not a floor cell",
+                    object.pos
+                )));
+            }
+            if self.objects[..i]
+                .iter()
+                .any(|other| other.pos == object.pos)
+            {
+                return Err(LevelError::Invalid(format!(
+                    "object {i} shares {:?} with another object",
+                    object.pos
+                )));
+            }
+            if !matches!(object.kind, ObjectKind::Plate { .. })
+                && self.robots.iter().any(|robot| robot.pos == object.pos)
+            {
+                return Err(LevelError::Invalid(format!(
+                    "object {i} is on top of a robot at {:?}",
+                    object.pos
+                )));
+            }
+        }
+        if let WinCondition::RobotAt { robot, .. } = self.win_condition {
+            if robot >= self.robots.len() {
+                return Err(LevelError::Invalid(format!(
+                    "win condition refers to robot {robot} which does not exist"
+                )));
+            }
+        }
+        Ok(())
+    }
+    /// Takes robot `index` out of the level. A `RobotAt` win condition keeps pointing at the
+    /// same robot, or goes back to the default if that robot is the one removed.
+    pub fn remove_robot(&mut self, index: usize) {
+        self.robots.remove(index);
+        if let WinCondition::RobotAt { robot, pos } = self.win_condition {
+            self.win_condition = match robot.cmp(&index) {
+                Ordering::Less => WinCondition::RobotAt { robot, pos },
+                Ordering::Equal => WinCondition::AllRobotsOnGoals,
+                Ordering::Greater => WinCondition::RobotAt {
+                    robot: robot - 1,
+                    pos,
+                },
+            };
+        }
+    }
+    pub fn to_world(&self) -> World {
+        World {
+            robots: self
+                .robots
+                .iter()
+                .map(|spawn| {
+                    let mut robot = Robot::new(
+                        spawn.pos,
+                        spawn.rotation,
+                        spawn.brain_width,
+                        spawn.brain_height,
+                        spawn.total_instructions.clone(),
+                    );
+                    robot.brain.wrap = self.brain_wrap;
+                    robot
+                })
+                .collect(),
+            map: self.map.clone(),
+            objects: self
+                .objects
+                .iter()
+                .map(|spawn| Object::new(spawn.kind, spawn.pos))
+                .collect(),
+            keys: [false; CHANNELS],
+            signals: Signals::default(),
+            win_condition: self.win_condition,
+            status: LevelStatus::Running,
+            ticks: 0,
+            trace: None,
+            loop_check: LoopCheck::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Level, LevelError, WinCondition};
+
+    const TWO_ROBOTS: &str = "(
+        map: (width: 2, height: 1, cells: [Empty, Empty]),
+        robots: [
+            (pos: (0, 0), rotation: Up, brain_width: 1, brain_height: 1, total_instructions: []),
+            (pos: (POS), rotation: Up, brain_width: 1, brain_height: 1, total_instructions: []),
+        ],
+    )";
+
+    #[test]
+    fn robots_on_different_cells() {
+        assert!(Level::parse(&TWO_ROBOTS.replace("POS", "1, 0")).is_ok());
+    }
+
+    #[test]
+    fn robots_sharing_a_cell() {
+        assert!(matches!(
+            Level::parse(&TWO_ROBOTS.replace("POS", "0, 0")),
+            Err(LevelError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn removing_a_robot_keeps_the_win_condition_on_the_same_robot() {
+        let mut level = Level::parse(&TWO_ROBOTS.replace("POS", "1, 0")).unwrap();
+        level.win_condition = WinCondition::RobotAt {
+            robot: 1,
+            pos: (0, 0),
+        };
+        level.remove_robot(0);
+        assert_eq!(
+            level.win_condition,
+            WinCondition::RobotAt {
+                robot: 0,
+                pos: (0, 0)
+            }
+        );
+        level.remove_robot(0);
+        assert_eq!(level.win_condition, WinCondition::AllRobotsOnGoals);
+        assert!(level.validate().is_ok());
+    }
+}
@@ -0,0 +1,107 @@
+use raylib::prelude::*;
+use robot_coding_game::{Campaign, Progress};
+
+/// Screen area of each level in the list.
+fn level_rows(campaign: &Campaign) -> impl Iterator<Item = (usize, Rectangle)> {
+    (0..campaign.levels.len()).map(|i| {
+        (
+            i,
+            Rectangle {
+                x: 50.0,
+                y: 80.0 + i as f32 * 34.0,
+                width: 320.0,
+                height: 30.0,
+            },
+        )
+    })
+}
+
+/// Returns the level the player clicked on, if it is unlocked.
+pub fn level_select_inputs(
+    rl: &RaylibHandle,
+    campaign: &Campaign,
+    progress: &Progress,
+    mouse_pos: Vector2,
+) -> Option<usize> {
+    if !rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+        return None;
+    }
+    level_rows(campaign)
+        .find(|(_, row)| row.check_collision_point_rec(mouse_pos))
+        .map(|(i, _)| i)
+        .filter(|&i| campaign.is_unlocked(i, progress))
+}
+
+pub fn draw_level_select(
+    d: &mut RaylibDrawHandle,
+    campaign: &Campaign,
+    progress: &Progress,
+    mouse_pos: Vector2,
+) {
+    d.draw_text("Select a level", 50, 30, 30, Color::WHITE);
+    for (i, row) in level_rows(campaign) {
+        let level = &campaign.levels[i];
+        let unlocked = campaign.is_unlocked(i, progress);
+        let background = if unlocked && row.check_collision_point_rec(mouse_pos) {
+            Color::new(70, 70, 70, 255)
+        } else {
+            Color::new(45, 45, 45, 255)
+        };
+        d.draw_rectangle_rec(row, background);
+        let text_color = match unlocked {
+            true => Color::WHITE,
+            false => Color::GRAY,
+        };
+        d.draw_text(
+            &format!("{}. {}", i + 1, level.title()),
+            row.x as i32 + 8,
+            row.y as i32 + 6,
+            20,
+            text_color,
+        );
+        let status = if progress.is_solved(&level.name) {
+            "solved"
+        } else if unlocked {
+            ""
+        } else {
+            "locked"
+        };
+        d.draw_text(
+            status,
+            (row.x + row.width) as i32 - 70,
+            row.y as i32 + 8,
+            16,
+            match status {
+                "solved" => Color::new(90, 200, 110, 255),
+                _ => Color::GRAY,
+            },
+        );
+    }
+    let y = 80 + campaign.levels.len() as i32 * 34 + 10;
+    draw_skipped(d, campaign, y);
+}
+
+/// Shown instead of the level list when not a single level could be loaded.
+pub fn draw_no_levels(d: &mut RaylibDrawHandle, campaign: &Campaign) {
+    d.draw_text(
+        "No levels to play",
+        50,
+        30,
+        30,
+        Color::new(220, 80, 80, 255),
+    );
+    draw_skipped(d, campaign, 80);
+}
+
+/// Lists the level files that were left out of the campaign, starting at height `y`.
+fn draw_skipped(d: &mut RaylibDrawHandle, campaign: &Campaign, y: i32) {
+    for (i, error) in campaign.skipped.iter().enumerate() {
+        d.draw_text(
+            &format!("Skipped {error}"),
+            50,
+            y + i as i32 * 18,
+            14,
+            Color::new(220, 80, 80, 255),
+        );
+    }
+}
@@ -0,0 +1,461 @@
+#![warn(clippy::semicolon_if_nothing_returned)]
+#![allow(clippy::too_many_arguments)]
+
+//! Headless simulation of the robot puzzle. Nothing in here depends on raylib or rodio so
+//! puzzles can be run from tests, scripts and servers without a window.
+
+pub use campaign::*;
+pub use instructions::*;
+pub use level::*;
+pub use program::*;
+pub use region::*;
+pub use ron_file::FileError;
+pub use score::*;
+use serde::{Deserialize, Serialize};
+pub use stuck::*;
+pub use trace::*;
+
+mod campaign;
+mod instructions;
+mod level;
+mod movement;
+mod program;
+mod region;
+mod ron_file;
+mod score;
+mod stuck;
+mod trace;
+
+/// Number of channels an instruction can pick from. Every brain has one counter per channel.
+pub const CHANNELS: usize = 4;
+/// Counters stop going up here, so a loop that keeps incrementing one still comes back to an
+/// earlier state and `LoopCheck` can find it.
+pub const MAX_COUNTER: u32 = 255;
+
+#[derive(Clone, Debug)]
+pub struct World {
+    pub robots: Vec<Robot>,
+    pub map: Map,
+    pub objects: Vec<Object>,
+    /// Channels whose key has been picked up. Doors on these channels stay open.
+    pub keys: [bool; CHANNELS],
+    pub signals: Signals,
+    pub win_condition: WinCondition,
+    pub status: LevelStatus,
+    /// Ticks simulated since the level was started or reset.
+    pub ticks: u32,
+    /// Where ticks are recorded while set. Not touched by `reset_to`, so a run can still be
+    /// looked at after resetting.
+    pub trace: Option<Trace>,
+    pub loop_check: LoopCheck,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Map {
+    pub width: usize,
+    pub height: usize,
+    pub cells: Vec<Cell>,
+    /// Items lying on the floor, at most one per cell.
+    #[serde(default)]
+    pub items: Vec<((i32, i32), Item)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Cell {
+    Empty,
+    Wall,
+    /// A hole in the floor. Robots that move into it fall and the level is failed.
+    Gap,
+    Goal,
+}
+
+/// Channels robots have signalled on. A signal raised during one tick is seen by every
+/// `WaitForSignal` on the following tick, so it doesn't matter which robot runs first.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Signals {
+    /// Raised during the previous tick. This is what `WaitForSignal` checks.
+    pub previous: [bool; CHANNELS],
+    /// Raised so far during the current tick.
+    pub current: [bool; CHANNELS],
+}
+
+/// Something a robot can pick up with `Grab` and put down with `Drop`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Item {
+    Gem,
+    Battery,
+}
+
+/// Something on the map that can move or change during a run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Object {
+    pub kind: ObjectKind,
+    pub pos: (i32, i32),
+    /// Position before the last tick, so renderers can interpolate movement.
+    pub last_pos: (i32, i32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ObjectKind {
+    /// Pushed one cell by a robot moving into it. A crate pushed into a gap falls in and fills
+    /// it, turning the gap into floor.
+    Crate,
+    /// Picked up by the first robot to move onto it, opening every door on its channel for the
+    /// rest of the run.
+    Key { channel: usize },
+    /// Blocks robots and crates while closed. Opens while its key has been picked up or a plate
+    /// on its channel is pressed, and never closes on something standing in it.
+    Door {
+        channel: usize,
+        #[serde(default)]
+        open: bool,
+    },
+    /// Pressed while a robot or crate stands on it.
+    Plate { channel: usize },
+}
+
+/// What has to be true after a tick for the level to count as solved.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum WinCondition {
+    #[default]
+    AllRobotsOnGoals,
+    RobotAt {
+        robot: usize,
+        pos: (i32, i32),
+    },
+    /// Every item lies on a `Goal` cell and no robot is still carrying one.
+    ItemsOnGoals,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LevelStatus {
+    Running,
+    Complete,
+    /// A robot fell into a `Cell::Gap`.
+    Failed,
+    /// The world came back to a state it was in before, so the level can't be solved any more.
+    Stuck(Stuck),
+}
+
+#[derive(Clone, Debug)]
+pub struct Robot {
+    pub brain: Brain,
+    pub pos: (i32, i32),
+    pub rotation: Rotation,
+    /// Position and rotation before the last tick, so renderers can interpolate movement.
+    pub last_pos: (i32, i32),
+    pub last_rotation: Rotation,
+    pub carrying: Option<Item>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Brain {
+    pub width: u32,
+    pub height: u32,
+    pub instructions: Vec<Instruction>,
+    pub total_instructions: Vec<(usize, InstructionType)>,
+    pub reader: Reader,
+    pub counters: [u32; CHANNELS],
+    /// Whether the reader wraps around to the opposite edge instead of stopping at it.
+    pub wrap: bool,
+}
+#[derive(Clone, Copy, Debug)]
+pub struct Reader {
+    pub pos: (i32, i32),
+    pub rotation: Rotation,
+    /// Whether the instruction under the reader runs on the next tick. Cleared when the reader
+    /// could not advance onto a non-`None` cell.
+    pub read_next: bool,
+    /// A halted robot is skipped by `World::step` for the rest of the run.
+    pub halted: bool,
+    /// Number of ticks this robot has run for.
+    pub cycles: u32,
+    /// Ticks left before the reader moves on from a `Wait` instruction.
+    pub waiting: u32,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Instruction {
+    pub instruction_type: InstructionType,
+    pub rotation: Rotation,
+    pub edit: bool,
+    /// Which counter, `Jump` pair or signal the instruction refers to, or how long a `Wait`
+    /// lasts, see `Instruction::wait_ticks`. Programs saved before jumps and signals existed
+    /// call this `counter`.
+    #[serde(default, alias = "counter")]
+    pub channel: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InstructionType {
+    Move,
+    Back,
+    Direction,
+    RotateLeft,
+    RotateRight,
+    /// Points the reader along the instruction's rotation if the cell in front of the robot is
+    /// a wall or closed door, otherwise the reader carries on unchanged.
+    WallAhead,
+    /// Like `WallAhead`, but checks for a gap.
+    GapAhead,
+    /// Like `WallAhead`, but checks whether the robot is standing on a goal.
+    OnGoal,
+    /// Adds one to the instruction's counter, stopping at `MAX_COUNTER`.
+    Increment,
+    /// Subtracts one from the instruction's counter, stopping at zero.
+    Decrement,
+    /// Points the reader along the instruction's rotation if its counter is zero.
+    BranchIfZero,
+    /// Moves the reader to the next `Jump` on the same channel, from where it carries on in
+    /// its current direction.
+    Jump,
+    /// Keeps the reader in place for a number of ticks set by the instruction's channel, see
+    /// `Instruction::wait_ticks`.
+    Wait,
+    /// Stops the robot for the rest of the run.
+    Halt,
+    /// Raises a signal on the instruction's channel for every robot to see on the next tick.
+    Signal,
+    /// Keeps the reader in place until a signal on the instruction's channel was raised on the
+    /// previous tick.
+    WaitForSignal,
+    /// Picks up the item under the robot if it isn't already carrying one.
+    Grab,
+    /// Puts the carried item down under the robot if there isn't one there already.
+    Drop,
+    None,
+    Blank,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Rotation {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Instruction {
+    /// How many ticks a `Wait` instruction takes. The length is picked like a channel, so the
+    /// first channel waits one tick and the last one `CHANNELS` ticks.
+    pub fn wait_ticks(&self) -> u32 {
+        self.channel as u32 + 1
+    }
+}
+
+impl World {
+    pub fn new(map_width: usize, map_height: usize, robots: Vec<Robot>) -> World {
+        World {
+            robots,
+            map: Map::new(map_width, map_height),
+            objects: vec![],
+            keys: [false; CHANNELS],
+            signals: Signals::default(),
+            win_condition: WinCondition::default(),
+            status: LevelStatus::Running,
+            ticks: 0,
+            trace: None,
+            loop_check: LoopCheck::default(),
+        }
+    }
+}
+
+impl Object {
+    pub fn new(kind: ObjectKind, pos: (i32, i32)) -> Object {
+        Object {
+            kind,
+            pos,
+            last_pos: pos,
+        }
+    }
+    /// Whether robots and crates can't move into this object's cell.
+    pub fn is_solid(&self) -> bool {
+        match self.kind {
+            ObjectKind::Crate => true,
+            ObjectKind::Key { .. } => false,
+            ObjectKind::Door { open, .. } => !open,
+            ObjectKind::Plate { .. } => false,
+        }
+    }
+}
+
+impl Map {
+    pub fn new(width: usize, height: usize) -> Map {
+        let mut map: Vec<Cell> = vec![];
+        for _ in 0..(width * height) {
+            map.push(Cell::Empty);
+        }
+        Map {
+            width,
+            height,
+            cells: map,
+            items: vec![],
+        }
+    }
+    pub fn get_cell_type(&self, x: i32, y: i32) -> Cell {
+        if x < 0 || x >= self.width as i32 || y < 0 || y >= self.height as i32 {
+            return Cell::Wall;
+        }
+        let index = x + y * self.width as i32;
+        self.cells[index as usize]
+    }
+    /// Changes the cell at `x`, `y`. Does nothing out of bounds.
+    pub fn set_cell_type(&mut self, x: i32, y: i32, cell: Cell) {
+        if x < 0 || x >= self.width as i32 || y < 0 || y >= self.height as i32 {
+            return;
+        }
+        let index = x + y * self.width as i32;
+        self.cells[index as usize] = cell;
+    }
+    /// Changes the size of the map, keeping the cells and items that still fit and filling new
+    /// cells with `Cell::Empty`.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let mut map = Map::new(width, height);
+        for y in 0..height.min(self.height) as i32 {
+            for x in 0..width.min(self.width) as i32 {
+                map.set_cell_type(x, y, self.get_cell_type(x, y));
+            }
+        }
+        map.items = std::mem::take(&mut self.items);
+        map.items
+            .retain(|(pos, _)| (pos.0 as usize) < width && (pos.1 as usize) < height);
+        *self = map;
+    }
+    pub fn get_item(&self, pos: (i32, i32)) -> Option<Item> {
+        self.items
+            .iter()
+            .find(|(item_pos, _)| *item_pos == pos)
+            .map(|(_, item)| *item)
+    }
+    /// Removes and returns the item at `pos`, if there is one.
+    pub fn take_item(&mut self, pos: (i32, i32)) -> Option<Item> {
+        let index = self
+            .items
+            .iter()
+            .position(|(item_pos, _)| *item_pos == pos)?;
+        Some(self.items.remove(index).1)
+    }
+}
+
+impl Robot {
+    pub fn new(
+        pos: (i32, i32),
+        rotation: Rotation,
+        brain_width: u32,
+        brain_height: u32,
+        total_instructions: Vec<(usize, InstructionType)>,
+    ) -> Robot {
+        Robot {
+            brain: Brain::new(brain_width, brain_height, total_instructions),
+            pos,
+            rotation,
+            last_pos: pos,
+            last_rotation: rotation,
+            carrying: None,
+        }
+    }
+}
+
+impl Brain {
+    pub fn new(
+        width: u32,
+        height: u32,
+        total_instructions: Vec<(usize, InstructionType)>,
+    ) -> Brain {
+        let mut instructions: Vec<Instruction> = vec![];
+        for _ in 0..width * height {
+            instructions.push(Instruction {
+                instruction_type: InstructionType::None,
+                rotation: Rotation::Up,
+                edit: true,
+                channel: 0,
+            });
+        }
+        Brain {
+            width,
+            height,
+            instructions,
+            reader: Reader {
+                pos: (0, 0),
+                rotation: Rotation::Up,
+                read_next: true,
+                halted: false,
+                cycles: 0,
+                waiting: 0,
+            },
+            total_instructions,
+            counters: [0; CHANNELS],
+            wrap: false,
+        }
+    }
+    pub fn get_instruction(&self, pos: (i32, i32)) -> &Instruction {
+        if !self.in_bounds(pos) {
+            return &Instruction {
+                instruction_type: InstructionType::None,
+                rotation: Rotation::Up,
+                edit: false,
+                channel: 0,
+            };
+        }
+        let index = (pos.0 + pos.1 * self.width as i32) as usize;
+        &self.instructions[index]
+    }
+    pub fn in_bounds(&self, pos: (i32, i32)) -> bool {
+        if pos.0 >= 0 && pos.0 < self.width as i32 && pos.1 >= 0 && pos.1 < self.height as i32 {
+            return true;
+        }
+        false
+    }
+    pub fn get_avalible_instructions(&self) -> Vec<(usize, InstructionType)> {
+        let mut total_instructions = self.total_instructions.clone();
+        total_instructions.insert(0, (1, InstructionType::None));
+
+        for avalible_instruction in &mut total_instructions {
+            if avalible_instruction.1 != InstructionType::None {
+                for instruction in &self.instructions {
+                    let other_instruction_type = instruction.instruction_type;
+                    if avalible_instruction.1 == other_instruction_type
+                        && avalible_instruction.0 != 0
+                    {
+                        avalible_instruction.0 -= 1;
+                    }
+                }
+            }
+        }
+        total_instructions.retain(|instruction| instruction.0 != 0);
+        total_instructions
+    }
+    /// Returns the first instruction type that `instructions` uses more often than the budget in
+    /// `total_instructions` allows.
+    pub fn over_budget(&self, instructions: &[Instruction]) -> Option<InstructionType> {
+        for instruction in instructions {
+            let instruction_type = instruction.instruction_type;
+            if instruction_type == InstructionType::None {
+                continue;
+            }
+            let used = instructions
+                .iter()
+                .filter(|other| other.instruction_type == instruction_type)
+                .count();
+            let allowed: usize = self
+                .total_instructions
+                .iter()
+                .filter(|total| total.1 == instruction_type)
+                .map(|total| total.0)
+                .sum();
+            if used > allowed {
+                return Some(instruction_type);
+            }
+        }
+        None
+    }
+    pub fn get_instruction_count(&self, instruction: InstructionType) -> usize {
+        let avalible_instructions = self.get_avalible_instructions();
+        let mut count = 0;
+        for avalible_instruction in avalible_instructions {
+            if avalible_instruction.1 == instruction {
+                count = avalible_instruction.0;
+            }
+        }
+        count
+    }
+}
@@ -3,198 +3,30 @@
 
 use draw_brain::*;
 use draw_map::*;
+use history::*;
 use inputs::*;
-use instructions::*;
+use level_select::*;
+use map_editor::*;
+use notice::*;
 use raylib::prelude::*;
-use rodio::{
-    source::{SamplesConverter, Source},
-    Decoder, OutputStream,
+use replay::*;
+use results::*;
+use robot_coding_game::*;
+use rodio::OutputStream;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
 };
 
 mod draw_brain;
 mod draw_map;
+mod history;
 mod inputs;
-mod instructions;
-
-struct World {
-    robots: Vec<Robot>,
-    map: Map,
-}
-
-#[derive(Clone, Debug)]
-struct Map {
-    width: usize,
-    height: usize,
-    cells: Vec<Cell>,
-}
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum Cell {
-    Empty,
-    Wall,
-    Gap,
-}
-
-#[derive(Clone, Debug)]
-struct Robot {
-    brain: Brain,
-    pos: (i32, i32),
-    rotation: Rotation,
-}
-
-#[derive(Clone, Debug)]
-struct Brain {
-    width: u32,
-    height: u32,
-    instructions: Vec<Instruction>,
-    total_instructions: Vec<(usize, InstructionType)>,
-    reader: Reader,
-}
-#[derive(Clone, Copy, Debug)]
-struct Reader {
-    pos: (i32, i32),
-    rotation: Rotation,
-}
-#[derive(Clone, Copy, Debug, PartialEq)]
-struct Instruction {
-    instruction_type: InstructionType,
-    rotation: Rotation,
-    edit: bool,
-}
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum InstructionType {
-    Move,
-    Back,
-    Direction,
-    RotateLeft,
-    RotateRight,
-    None,
-    Blank,
-}
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum Rotation {
-    Up,
-    Right,
-    Down,
-    Left,
-}
-
-impl World {
-    fn new(map_width: usize, map_height: usize, robots: Vec<Robot>) -> World {
-        World {
-            robots,
-            map: Map::new(map_width, map_height),
-        }
-    }
-}
-
-impl Map {
-    fn new(width: usize, height: usize) -> Map {
-        let mut map: Vec<Cell> = vec![];
-        for _ in 0..(width * height) {
-            map.push(Cell::Empty);
-        }
-        Map {
-            width,
-            height,
-            cells: map,
-        }
-    }
-    fn get_cell_type(&self, x: i32, y: i32) -> Cell {
-        if x < 0 || x >= self.width as i32 || y < 0 || y >= self.height as i32 {
-            return Cell::Wall;
-        }
-        let index = x + y * self.width as i32;
-        self.cells.clone()[index as usize]
-    }
-}
-
-impl Robot {
-    fn new(
-        pos: (i32, i32),
-        rotation: Rotation,
-        brain_width: u32,
-        brain_height: u32,
-        total_instructions: Vec<(usize, InstructionType)>,
-    ) -> Robot {
-        Robot {
-            brain: Brain::new(brain_width, brain_height, total_instructions),
-            pos,
-            rotation,
-        }
-    }
-}
-
-impl Brain {
-    fn new(width: u32, height: u32, total_instructions: Vec<(usize, InstructionType)>) -> Brain {
-        let mut instructions: Vec<Instruction> = vec![];
-        for _ in 0..width * height {
-            instructions.push(Instruction {
-                instruction_type: InstructionType::None,
-                rotation: Rotation::Up,
-                edit: true,
-            });
-        }
-        Brain {
-            width,
-            height,
-            instructions,
-            reader: Reader {
-                pos: (0, 0),
-                rotation: Rotation::Up,
-            },
-            total_instructions,
-        }
-    }
-    fn get_instruction(&self, pos: (i32, i32)) -> &Instruction {
-        if !self.in_bounds(pos) {
-            return &Instruction {
-                instruction_type: InstructionType::None,
-                rotation: Rotation::Up,
-                edit: false,
-            };
-        }
-        let index = (pos.0 + pos.1 * self.width as i32) as usize;
-        &self.instructions[index]
-    }
-    fn in_bounds(&self, pos: (i32, i32)) -> bool {
-        if pos.0 >= 0 && pos.0 < self.width as i32 && pos.1 >= 0 && pos.1 < self.height as i32 {
-            return true;
-        }
-        false
-    }
-    fn get_avalible_instructions(&self) -> Vec<(usize, InstructionType)> {
-        let mut total_instructions = self.total_instructions.clone();
-        total_instructions.insert(0, (1, InstructionType::None));
-
-        for avalible_instruction in &mut total_instructions {
-            if avalible_instruction.1 != InstructionType::None {
-                for instruction in &self.instructions {
-                    let other_instruction_type = instruction.instruction_type;
-                    if avalible_instruction.1 == other_instruction_type
-                        && avalible_instruction.0 != 0
-                    {
-                        avalible_instruction.0 -= 1;
-                    }
-                }
-            }
-        }
-        total_instructions.retain(|instruction| instruction.0 != 0);
-        total_instructions
-    }
-    fn get_instruction_count(&self, instruction: InstructionType) -> usize {
-        let avalible_instructions = self.get_avalible_instructions();
-        let mut count = 0;
-        for avalible_instruction in avalible_instructions {
-            if avalible_instruction.1 == instruction {
-                count = avalible_instruction.0;
-            }
-        }
-        count
-    }
-}
+mod level_select;
+mod map_editor;
+mod notice;
+mod replay;
+mod results;
 
 struct Assets {
     brain_edge: Texture2D,
@@ -207,7 +39,21 @@ struct Assets {
     back_instruction: Texture2D,
     right_instruction: Texture2D,
     left_instruction: Texture2D,
+    wall_sensor_instruction: Texture2D,
+    gap_sensor_instruction: Texture2D,
+    goal_sensor_instruction: Texture2D,
+    wait_instruction: Texture2D,
+    halt_instruction: Texture2D,
+    increment_instruction: Texture2D,
+    decrement_instruction: Texture2D,
+    branch_zero_instruction: Texture2D,
+    jump_instruction: Texture2D,
+    signal_instruction: Texture2D,
+    wait_signal_instruction: Texture2D,
+    grab_instruction: Texture2D,
+    drop_instruction: Texture2D,
     reader: Texture2D,
+    robot: Texture2D,
     font: WeakFont,
     map: MapAssets,
 }
@@ -238,12 +84,99 @@ struct WallAssets {
     corner_straight: Texture2D,
 }
 
+/// Where the player's progress through the campaign is saved.
+const PROGRESS_PATH: &str = "progress.ron";
+
+enum Screen {
+    LevelSelect,
+    /// Playing a level of the campaign, or `None` for a level changed in the map editor.
+    Playing {
+        campaign_level: Option<usize>,
+    },
+}
+
+struct BoardView {
+    pos: Vector2,
+    width: f32,
+    scale: f32,
+}
+
+/// Seconds per tick at 1x speed.
+const BASE_UPDATE_DT: f32 = 0.5;
+/// Speed multipliers the player can cycle through. `None` runs as many ticks as fit in a frame.
+const SPEEDS: [Option<f32>; 8] = [
+    Some(0.25),
+    Some(0.5),
+    Some(1.0),
+    Some(2.0),
+    Some(4.0),
+    Some(8.0),
+    Some(16.0),
+    None,
+];
+
+struct Simulation {
+    stepping: bool,
+    /// Index into `SPEEDS`.
+    speed: usize,
+    time_since_last_step: f32,
+    /// The world as it was when the current run started, restored by a reset.
+    initial_world: Option<World>,
+}
+
+impl Simulation {
+    fn update_dt(&self) -> Option<f32> {
+        SPEEDS[self.speed].map(|speed| BASE_UPDATE_DT / speed)
+    }
+    fn speed_text(&self) -> String {
+        match SPEEDS[self.speed] {
+            Some(speed) => format!("{speed}x"),
+            None => "max".to_string(),
+        }
+    }
+}
+
+/// What the left mouse button is doing in the open brain, besides painting instructions.
+#[derive(Clone, Copy, PartialEq)]
+enum BrainDrag {
+    None,
+    /// Dragging out a new selection.
+    Selecting,
+    /// Dragging the selection, which was grabbed at the brain cell `from`.
+    Moving {
+        from: (i32, i32),
+    },
+}
+
 struct BrainEdit {
     pos: Vector2,
     id: Option<usize>,
     size: f32,
     scale: f32,
     selected_instruction: Instruction,
+    /// Edit history of each robot's brain, by robot index.
+    histories: HashMap<usize, BrainHistory>,
+    /// Opposite corners of the selected rectangle of brain cells.
+    selection: Option<((i32, i32), (i32, i32))>,
+    drag: BrainDrag,
+    clipboard: Option<Region>,
+}
+
+/// Swaps `world` for a fresh copy of `level` and clears everything left over from the last run.
+fn start_level(
+    level: &Level,
+    world: &mut World,
+    simulation: &mut Simulation,
+    brain_edit: &mut BrainEdit,
+) {
+    *world = level.to_world();
+    simulation.stepping = false;
+    simulation.time_since_last_step = 0.0;
+    simulation.initial_world = None;
+    brain_edit.id = None;
+    brain_edit.selection = None;
+    brain_edit.drag = BrainDrag::None;
+    brain_edit.histories.clear();
 }
 
 fn main() {
@@ -254,33 +187,39 @@ fn main() {
         .vsync()
         .title("Robotery")
         .build();
+    rl.set_exit_key(None);
 
     let (_stream, sound_handle) = OutputStream::try_default().unwrap();
-    let mut world = World::new(
-        6,
-        6,
-        vec![Robot::new(
-            (0, 0),
-            Rotation::Up,
-            5,
-            5,
-            vec![
-                (5, InstructionType::Direction),
-                (5, InstructionType::Move),
-                (5, InstructionType::Back),
-                (5, InstructionType::RotateLeft),
-                (5, InstructionType::RotateRight),
-            ],
-        )],
-    );
-
-    world.map.cells[world.map.width * 0 + 1] = Cell::Wall;
-    world.map.cells[world.map.width * 3 + 1] = Cell::Wall;
-    
-    world.map.cells[world.map.width * 1 + 1] = Cell::Wall;
-    world.map.cells[world.map.width * 2 + 1] = Cell::Wall;
-    world.map.cells[world.map.width * 2 + 2] = Cell::Wall;
-    world.map.cells[world.map.width * 2 + 0] = Cell::Wall;
+    let mut notice = Notice::default();
+    let campaign = Campaign::load("Assets/levels").unwrap_or_else(|error| Campaign {
+        levels: vec![],
+        skipped: vec![error],
+    });
+    let mut progress = Progress::load(PROGRESS_PATH).unwrap_or_else(|error| {
+        notice.error(format!("progress: {error}"));
+        Progress::default()
+    });
+    let Some(first_level) = campaign.levels.first() else {
+        while !rl.window_should_close() {
+            let mut d = rl.begin_drawing(&thread);
+            d.clear_background(Color::new(20, 20, 20, 255));
+            draw_no_levels(&mut d, &campaign);
+        }
+        return;
+    };
+    let mut screen = Screen::LevelSelect;
+    let mut level = first_level.level.clone();
+    let mut world = level.to_world();
+    let mut map_editor: Option<MapEditor> = None;
+    let mut replay: Option<Replay> = None;
+    // Score of the last completed run
+    let mut results: Option<Score> = None;
+
+    let board = BoardView {
+        pos: Vector2::new(50.0, 50.0),
+        width: 500.0,
+        scale: 1.0,
+    };
 
     let mut brain_edit = BrainEdit {
         pos: Vector2 { x: 100.0, y: 450.0 },
@@ -291,7 +230,12 @@ fn main() {
             instruction_type: InstructionType::None,
             rotation: Rotation::Up,
             edit: true,
+            channel: 0,
         },
+        histories: HashMap::new(),
+        selection: None,
+        drag: BrainDrag::None,
+        clipboard: None,
     };
 
     let assets = Assets {
@@ -323,7 +267,47 @@ fn main() {
         left_instruction: rl
             .load_texture(&thread, "Assets/left_instruction.png")
             .unwrap(),
+        wall_sensor_instruction: rl
+            .load_texture(&thread, "Assets/wall_sensor_instruction.png")
+            .unwrap(),
+        gap_sensor_instruction: rl
+            .load_texture(&thread, "Assets/gap_sensor_instruction.png")
+            .unwrap(),
+        goal_sensor_instruction: rl
+            .load_texture(&thread, "Assets/goal_sensor_instruction.png")
+            .unwrap(),
+        wait_instruction: rl
+            .load_texture(&thread, "Assets/wait_instruction.png")
+            .unwrap(),
+        halt_instruction: rl
+            .load_texture(&thread, "Assets/halt_instruction.png")
+            .unwrap(),
+        increment_instruction: rl
+            .load_texture(&thread, "Assets/increment_instruction.png")
+            .unwrap(),
+        decrement_instruction: rl
+            .load_texture(&thread, "Assets/decrement_instruction.png")
+            .unwrap(),
+        branch_zero_instruction: rl
+            .load_texture(&thread, "Assets/branch_zero_instruction.png")
+            .unwrap(),
+        jump_instruction: rl
+            .load_texture(&thread, "Assets/jump_instruction.png")
+            .unwrap(),
+        signal_instruction: rl
+            .load_texture(&thread, "Assets/signal_instruction.png")
+            .unwrap(),
+        wait_signal_instruction: rl
+            .load_texture(&thread, "Assets/wait_signal_instruction.png")
+            .unwrap(),
+        grab_instruction: rl
+            .load_texture(&thread, "Assets/grab_instruction.png")
+            .unwrap(),
+        drop_instruction: rl
+            .load_texture(&thread, "Assets/drop_instruction.png")
+            .unwrap(),
         reader: rl.load_texture(&thread, "Assets/reader.png").unwrap(),
+        robot: rl.load_texture(&thread, "Assets/robot.png").unwrap(),
         font: rl.get_font_default(),
         map: MapAssets {
             empty: EmptyAssets {
@@ -366,43 +350,162 @@ fn main() {
                 corner_inside: rl
                     .load_texture(&thread, "Assets/map/wall/corner_inside.png")
                     .unwrap(),
-                corner_outside:rl
-                .load_texture(&thread, "Assets/map/wall/corner_outside.png")
-                .unwrap(),
-                corner_straight:rl
-                .load_texture(&thread, "Assets/map/wall/corner_straight.png")
-                .unwrap(),
+                corner_outside: rl
+                    .load_texture(&thread, "Assets/map/wall/corner_outside.png")
+                    .unwrap(),
+                corner_straight: rl
+                    .load_texture(&thread, "Assets/map/wall/corner_straight.png")
+                    .unwrap(),
             },
         },
     };
-    let update_dt = 0.5;
-    let mut time_since_last_step = 0.0;
+    let mut simulation = Simulation {
+        stepping: false,
+        speed: 2,
+        time_since_last_step: 0.0,
+        initial_world: None,
+    };
 
-    let mut stepping = false;
-    let mut read_next = true;
     while !rl.window_should_close() {
         let width = rl.get_screen_width();
         let height = rl.get_screen_height();
         let dt = rl.get_frame_time();
 
         let mouse_pos = rl.get_mouse_position();
-
-        if stepping {
-            time_since_last_step += dt;
+        notice.update(dt);
+
+        if let Screen::LevelSelect = screen {
+            if let Some(index) = level_select_inputs(&rl, &campaign, &progress, mouse_pos) {
+                level = campaign.levels[index].level.clone();
+                start_level(&level, &mut world, &mut simulation, &mut brain_edit);
+                progress.restore(&campaign.levels[index].name, &mut world);
+                screen = Screen::Playing {
+                    campaign_level: Some(index),
+                };
+            }
+            let mut d = rl.begin_drawing(&thread);
+            d.clear_background(Color::new(20, 20, 20, 255));
+            draw_level_select(&mut d, &campaign, &progress, mouse_pos);
+            draw_notice(&mut d, &notice);
+            continue;
+        }
+        let Screen::Playing { campaign_level } = screen else {
+            unreachable!()
+        };
+
+        if replay.is_none() && rl.is_key_pressed(KeyboardKey::KEY_F2) {
+            match map_editor.take() {
+                None => map_editor = Some(MapEditor::new(level.clone())),
+                Some(editor) => match editor.level.validate() {
+                    Ok(()) => {
+                        level = editor.level;
+                        start_level(&level, &mut world, &mut simulation, &mut brain_edit);
+                        screen = Screen::Playing {
+                            campaign_level: None,
+                        };
+                    }
+                    Err(error) => {
+                        notice.error(error);
+                        map_editor = Some(editor);
+                    }
+                },
+            }
+        }
+        if let Some(editor) = &mut map_editor {
+            map_editor_inputs(&mut rl, editor, mouse_pos, &board, &mut notice);
+            let mut d = rl.begin_drawing(&thread);
+            d.clear_background(Color::new(20, 20, 20, 255));
+            draw_map_editor(&mut d, editor, &assets, &board);
+            draw_notice(&mut d, &notice);
+            continue;
+        }
+        if let Some(active) = &mut replay {
+            let close = replay_inputs(&rl, active, &mut notice);
+            let mut d = rl.begin_drawing(&thread);
+            d.clear_background(Color::new(20, 20, 20, 255));
+            draw_replay(&mut d, active, &assets, &board, &brain_edit, mouse_pos);
+            draw_notice(&mut d, &notice);
+            if close {
+                replay = None;
+            }
+            continue;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F3) {
+            // Without a run of its own the last saved trace is opened instead
+            let opened = match world.trace.clone().filter(|trace| !trace.ticks.is_empty()) {
+                Some(trace) => Replay::new(trace, brain_edit.id.unwrap_or(0))
+                    .map_err(|error| format!("trace: {error}")),
+                None => load_replay(brain_edit.id.unwrap_or(0)),
+            };
+            match opened {
+                Ok(opened) => {
+                    replay = Some(opened);
+                    simulation.stepping = false;
+                }
+                Err(error) => notice.error(error),
+            }
         }
 
-        (read_next, time_since_last_step) =
-            update_robots(&mut world, read_next, time_since_last_step, update_dt);
+        let was_running = world.status == LevelStatus::Running;
+        if simulation.stepping {
+            match simulation.update_dt() {
+                Some(update_dt) => {
+                    simulation.time_since_last_step =
+                        update_robots(&mut world, simulation.time_since_last_step + dt, update_dt);
+                }
+                None => {
+                    let frame_start = Instant::now();
+                    while world.status == LevelStatus::Running
+                        && frame_start.elapsed() < Duration::from_millis(10)
+                    {
+                        world.step();
+                    }
+                }
+            }
+        }
+        if world.status != LevelStatus::Running {
+            simulation.stepping = false;
+        }
 
-        stepping = inputs(
+        let leaving = rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) && brain_edit.id.is_none();
+        inputs(
             &mut rl,
             &mut world,
             &assets,
             mouse_pos,
+            &board,
             &mut brain_edit,
             &sound_handle,
-            stepping,
+            &mut simulation,
+            &level,
+            campaign_level.map_or("custom", |index| campaign.levels[index].name.as_str()),
+            &mut notice,
         );
+        if was_running && world.status == LevelStatus::Complete {
+            results = Some(world.score());
+            if let Some(index) = campaign_level {
+                progress.record(&campaign.levels[index].name, &world);
+                if let Err(error) = progress.save(PROGRESS_PATH) {
+                    notice.error(format!("progress: {error}"));
+                }
+            }
+        }
+        let next_level = campaign_level
+            .map(|index| index + 1)
+            .filter(|&next| next < campaign.levels.len());
+        if let Some(next) = next_level {
+            if world.status == LevelStatus::Complete && rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
+                level = campaign.levels[next].level.clone();
+                start_level(&level, &mut world, &mut simulation, &mut brain_edit);
+                progress.restore(&campaign.levels[next].name, &mut world);
+                screen = Screen::Playing {
+                    campaign_level: Some(next),
+                };
+            }
+        }
+        if leaving {
+            screen = Screen::LevelSelect;
+        }
 
         let mut d = rl.begin_drawing(&thread);
 
@@ -412,14 +515,65 @@ fn main() {
             &mut d,
             &world,
             &assets,
-            Vector2::new(50.0, 50.0),
-            500.0,
-            1.0,
+            board.pos,
+            board.width,
+            board.scale,
+            match simulation.update_dt() {
+                Some(update_dt) if simulation.stepping => {
+                    simulation.time_since_last_step / update_dt
+                }
+                _ => 1.0,
+            },
         );
+        let speed_text = if simulation.stepping {
+            format!("Speed: {}", simulation.speed_text())
+        } else {
+            format!("Paused ({})", simulation.speed_text())
+        };
+        d.draw_text(&speed_text, width - 200, 10, 20, Color::WHITE);
+        match world.status {
+            LevelStatus::Running => {}
+            LevelStatus::Complete => {
+                let text = match next_level {
+                    Some(_) => "Level complete! Enter: next level",
+                    None => "Level complete!",
+                };
+                d.draw_text(text, 50, 10, 30, Color::new(90, 200, 110, 255));
+            }
+            LevelStatus::Failed => {
+                d.draw_text("A robot fell!", 50, 10, 30, Color::new(220, 80, 80, 255));
+            }
+            LevelStatus::Stuck(stuck) => {
+                d.draw_text(
+                    &format!("Stuck: {stuck}"),
+                    50,
+                    10,
+                    30,
+                    Color::new(230, 160, 60, 255),
+                );
+            }
+        }
         if brain_edit.id.is_some() {
+            let robot = &world.robots[brain_edit.id.unwrap()];
+            let selection = brain_edit
+                .selection
+                .map(|(from, to)| match brain_edit.drag {
+                    BrainDrag::Moving { from: grabbed } => {
+                        let offset =
+                            move_offset(&robot.brain, &brain_edit, &assets, mouse_pos, grabbed);
+                        (
+                            (from.0 + offset.0, from.1 + offset.1),
+                            (to.0 + offset.0, to.1 + offset.1),
+                        )
+                    }
+                    _ => (from, to),
+                });
             draw_brain(
                 &mut d,
-                &world.robots[brain_edit.id.unwrap()].brain,
+                &robot.brain,
+                robot.carrying,
+                selection,
+                None,
                 brain_edit.pos,
                 brain_edit.size,
                 &assets,
@@ -428,5 +582,13 @@ fn main() {
                 brain_edit.scale,
             );
         }
+        if let Some(score) = results {
+            if world.status == LevelStatus::Complete && brain_edit.id.is_none() {
+                let best = campaign_level
+                    .and_then(|index| progress.best_score(&campaign.levels[index].name));
+                draw_results(&mut d, score, best, &level.reference_scores);
+            }
+        }
+        draw_notice(&mut d, &notice);
     }
 }
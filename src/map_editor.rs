@@ -0,0 +1,325 @@
+use raylib::prelude::*;
+use robot_coding_game::{Cell, InstructionType, Level, RobotSpawn, Rotation};
+
+use crate::{board_cell_at, draw_board, Assets, BoardView, Notice};
+
+/// Where `Ctrl+S` in the map editor writes the level.
+pub const EDITOR_LEVEL_PATH: &str = "custom_levels/level.ron";
+
+/// Cells painted with the number keys 1 to 4.
+const BRUSHES: [Cell; 4] = [Cell::Empty, Cell::Wall, Cell::Gap, Cell::Goal];
+
+/// Instructions a level can hand out, in the order the budget panel lists them.
+const BUDGET_TYPES: [InstructionType; 18] = [
+    InstructionType::Move,
+    InstructionType::Back,
+    InstructionType::Direction,
+    InstructionType::RotateLeft,
+    InstructionType::RotateRight,
+    InstructionType::WallAhead,
+    InstructionType::GapAhead,
+    InstructionType::OnGoal,
+    InstructionType::Increment,
+    InstructionType::Decrement,
+    InstructionType::BranchIfZero,
+    InstructionType::Jump,
+    InstructionType::Wait,
+    InstructionType::Halt,
+    InstructionType::Signal,
+    InstructionType::WaitForSignal,
+    InstructionType::Grab,
+    InstructionType::Drop,
+];
+
+const MAX_MAP_SIZE: usize = 32;
+const MAX_BRAIN_SIZE: u32 = 10;
+
+pub struct MapEditor {
+    pub level: Level,
+    brush: Cell,
+    /// Index of the robot spawn whose brain and budget are being edited.
+    robot: Option<usize>,
+}
+
+impl MapEditor {
+    pub fn new(level: Level) -> MapEditor {
+        MapEditor {
+            level,
+            brush: Cell::Wall,
+            robot: None,
+        }
+    }
+}
+
+/// Background of the selected robot's brain size and budget rows. Clicks on it don't reach the
+/// board underneath.
+fn budget_panel(screen_width: i32) -> Rectangle {
+    Rectangle {
+        x: screen_width as f32 - 185.0,
+        y: 25.0,
+        width: 180.0,
+        height: 30.0 + BUDGET_TYPES.len() as f32 * 20.0,
+    }
+}
+
+/// Rows of the budget panel down the right of the screen.
+fn budget_rows(screen_width: i32) -> impl Iterator<Item = (InstructionType, Rectangle)> {
+    BUDGET_TYPES
+        .into_iter()
+        .enumerate()
+        .map(move |(i, instruction_type)| {
+            (
+                instruction_type,
+                Rectangle {
+                    x: screen_width as f32 - 180.0,
+                    y: 50.0 + i as f32 * 20.0,
+                    width: 170.0,
+                    height: 20.0,
+                },
+            )
+        })
+}
+
+fn budget(spawn: &RobotSpawn, instruction_type: InstructionType) -> usize {
+    spawn
+        .total_instructions
+        .iter()
+        .filter(|total| total.1 == instruction_type)
+        .map(|total| total.0)
+        .sum()
+}
+
+fn set_budget(spawn: &mut RobotSpawn, instruction_type: InstructionType, count: usize) {
+    let totals = &mut spawn.total_instructions;
+    let index = totals
+        .iter()
+        .position(|total| total.1 == instruction_type)
+        .unwrap_or(totals.len());
+    totals.retain(|total| total.1 != instruction_type);
+    if count > 0 {
+        totals.insert(index.min(totals.len()), (count, instruction_type));
+    }
+}
+
+/// Painting cells with the left mouse button, placing and selecting robot spawns with the right
+/// one, resizing the map with the arrow keys and the selected robot's brain with Shift and the
+/// arrow keys, and saving with Ctrl+S.
+pub fn map_editor_inputs(
+    rl: &mut RaylibHandle,
+    editor: &mut MapEditor,
+    mouse_pos: Vector2,
+    board: &BoardView,
+    notice: &mut Notice,
+) {
+    let world = editor.level.to_world();
+    let cell = board_cell_at(&world, board.pos, board.width, board.scale, mouse_pos);
+    let over_panel = editor.robot.is_some()
+        && budget_panel(rl.get_screen_width()).check_collision_point_rec(mouse_pos);
+    let on_map = cell.0 >= 0
+        && cell.1 >= 0
+        && (cell.0 as usize) < world.map.width
+        && (cell.1 as usize) < world.map.height
+        && !over_panel;
+    let ctrl = rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL)
+        || rl.is_key_down(KeyboardKey::KEY_RIGHT_CONTROL);
+    let shift =
+        rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) || rl.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT);
+
+    let brush_keys = [
+        KeyboardKey::KEY_ONE,
+        KeyboardKey::KEY_TWO,
+        KeyboardKey::KEY_THREE,
+        KeyboardKey::KEY_FOUR,
+    ];
+    for (brush, key) in BRUSHES.into_iter().zip(brush_keys) {
+        if rl.is_key_pressed(key) {
+            editor.brush = brush;
+        }
+    }
+
+    //Cells
+    let robot_at = editor
+        .level
+        .robots
+        .iter()
+        .position(|robot| robot.pos == cell);
+    if on_map && rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
+        // Robots have to start on the floor
+        if robot_at.is_none() || matches!(editor.brush, Cell::Empty | Cell::Goal) {
+            editor.level.map.set_cell_type(cell.0, cell.1, editor.brush);
+        }
+    }
+
+    //Robots
+    if on_map && rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT) {
+        editor.robot = robot_at;
+        if robot_at.is_none()
+            && matches!(
+                editor.level.map.get_cell_type(cell.0, cell.1),
+                Cell::Empty | Cell::Goal
+            )
+        {
+            editor.level.robots.push(RobotSpawn {
+                pos: cell,
+                rotation: Rotation::Up,
+                brain_width: 5,
+                brain_height: 5,
+                total_instructions: vec![],
+            });
+            editor.robot = Some(editor.level.robots.len() - 1);
+        }
+    }
+    if let Some(robot) = editor.robot {
+        if rl.is_key_pressed(KeyboardKey::KEY_DELETE) {
+            editor.level.remove_robot(robot);
+            editor.robot = None;
+        }
+    }
+    if let Some(robot) = editor.robot.map(|robot| &mut editor.level.robots[robot]) {
+        if rl.get_mouse_wheel_move() > 0.0 {
+            robot.rotation = match robot.rotation {
+                Rotation::Up => Rotation::Right,
+                Rotation::Right => Rotation::Down,
+                Rotation::Down => Rotation::Left,
+                Rotation::Left => Rotation::Up,
+            }
+        } else if rl.get_mouse_wheel_move() < 0.0 {
+            robot.rotation = match robot.rotation {
+                Rotation::Up => Rotation::Left,
+                Rotation::Right => Rotation::Up,
+                Rotation::Down => Rotation::Right,
+                Rotation::Left => Rotation::Down,
+            }
+        }
+        if shift {
+            if rl.is_key_pressed(KeyboardKey::KEY_RIGHT) {
+                robot.brain_width = (robot.brain_width + 1).min(MAX_BRAIN_SIZE);
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_LEFT) {
+                robot.brain_width = (robot.brain_width - 1).max(1);
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_UP) {
+                robot.brain_height = (robot.brain_height + 1).min(MAX_BRAIN_SIZE);
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_DOWN) {
+                robot.brain_height = (robot.brain_height - 1).max(1);
+            }
+        }
+        for (instruction_type, row) in budget_rows(rl.get_screen_width()) {
+            if !row.check_collision_point_rec(mouse_pos) {
+                continue;
+            }
+            let count = budget(robot, instruction_type);
+            if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+                set_budget(robot, instruction_type, count + 1);
+            }
+            if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_RIGHT) {
+                set_budget(robot, instruction_type, count.saturating_sub(1));
+            }
+        }
+    }
+
+    //Map
+    if !shift {
+        let (mut width, mut height) = (editor.level.map.width, editor.level.map.height);
+        if rl.is_key_pressed(KeyboardKey::KEY_RIGHT) {
+            width = (width + 1).min(MAX_MAP_SIZE);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_LEFT) {
+            width = (width - 1).max(1);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_UP) {
+            height = (height + 1).min(MAX_MAP_SIZE);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_DOWN) {
+            height = (height - 1).max(1);
+        }
+        if (width, height) != (editor.level.map.width, editor.level.map.height) {
+            editor.level.resize(width, height);
+            editor.robot = editor
+                .robot
+                .filter(|&robot| robot < editor.level.robots.len());
+        }
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_W) {
+        editor.level.brain_wrap = !editor.level.brain_wrap;
+    }
+    if ctrl && rl.is_key_pressed(KeyboardKey::KEY_S) {
+        match editor.level.save(EDITOR_LEVEL_PATH) {
+            Ok(()) => notice.info(format!("Saved level to {EDITOR_LEVEL_PATH}")),
+            Err(error) => notice.error(error),
+        }
+    }
+}
+
+pub fn draw_map_editor(
+    d: &mut RaylibDrawHandle,
+    editor: &MapEditor,
+    assets: &Assets,
+    board: &BoardView,
+) {
+    let world = editor.level.to_world();
+    draw_board(d, &world, assets, board.pos, board.width, board.scale, 1.0);
+    let cell_size = board.width * board.scale / world.map.width as f32;
+    if let Some(robot) = editor.robot {
+        let pos = editor.level.robots[robot].pos;
+        d.draw_rectangle_lines_ex(
+            Rectangle {
+                x: board.pos.x + pos.0 as f32 * cell_size,
+                y: board.pos.y + (world.map.height as i32 - pos.1) as f32 * cell_size,
+                width: cell_size,
+                height: cell_size,
+            },
+            3.0,
+            Color::new(255, 200, 0, 255),
+        );
+    }
+
+    let wrap = match editor.level.brain_wrap {
+        true => "on",
+        false => "off",
+    };
+    d.draw_text(
+        &format!(
+            "Map editor - brush: {:?}   map: {}x{}   wrap: {wrap}",
+            editor.brush, world.map.width, world.map.height
+        ),
+        10,
+        10,
+        20,
+        Color::WHITE,
+    );
+    d.draw_text(
+        "1-4 brush, right click robot, arrows resize, W wrap, Ctrl+S save, F2 play",
+        10,
+        32,
+        10,
+        Color::LIGHTGRAY,
+    );
+
+    let Some(robot) = editor.robot.map(|robot| &editor.level.robots[robot]) else {
+        return;
+    };
+    let screen_width = d.get_screen_width();
+    d.draw_rectangle_rec(budget_panel(screen_width), Color::new(30, 30, 30, 230));
+    d.draw_text(
+        &format!("Brain: {}x{}", robot.brain_width, robot.brain_height),
+        screen_width - 180,
+        30,
+        16,
+        Color::WHITE,
+    );
+    for (instruction_type, row) in budget_rows(screen_width) {
+        let count = budget(robot, instruction_type);
+        d.draw_text(
+            &format!("{instruction_type:?}: {count}"),
+            row.x as i32,
+            row.y as i32 + 2,
+            16,
+            match count {
+                0 => Color::GRAY,
+                _ => Color::WHITE,
+            },
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use crate::{Cell, ObjectKind, World};
+
+impl World {
+    /// Decides which of the moves in `intents`, one per robot, are made this tick. The result
+    /// only depends on where everything is at the start of the tick, never on the order of
+    /// `robots`:
+    ///
+    /// - A move into a wall or closed door fails, as does pushing a crate that can't move.
+    /// - Moves that claim the same cell all fail, whether the cell is the one a robot moves
+    ///   into or the one a crate is pushed into. Two robots walking into each other head-on
+    ///   therefore both stay put.
+    /// - Two robots swapping places both stay put, as they would have to pass through each
+    ///   other.
+    /// - A robot can follow another one into the cell it leaves, so chains of robots move
+    ///   together. A chain stops as soon as one robot in it can't move, and a loop of three
+    ///   or more robots moves as one.
+    pub fn resolve_moves(&self, intents: &[Option<(i32, i32)>]) -> Vec<bool> {
+        let count = self.robots.len();
+        let mut moving = vec![false; count];
+        // The cell a crate is pushed into by each move.
+        let mut pushes: Vec<Option<(i32, i32)>> = vec![None; count];
+        for (i, intent) in intents.iter().enumerate() {
+            let Some(to) = *intent else {
+                continue;
+            };
+            let from = self.robots[i].pos;
+            if self.map.get_cell_type(to.0, to.1) == Cell::Wall {
+                continue;
+            }
+            if let Some(object) = self
+                .objects
+                .iter()
+                .find(|object| object.pos == to && object.is_solid())
+            {
+                let beyond = (to.0 * 2 - from.0, to.1 * 2 - from.1);
+                if object.kind != ObjectKind::Crate || !self.crate_can_enter(beyond) {
+                    continue;
+                }
+                pushes[i] = Some(beyond);
+            }
+            moving[i] = true;
+        }
+
+        let claims = |i: usize| [intents[i], pushes[i]];
+        let conflicts = |i: usize, j: usize| {
+            let swap =
+                intents[i] == Some(self.robots[j].pos) && intents[j] == Some(self.robots[i].pos);
+            let shared = claims(i)
+                .iter()
+                .flatten()
+                .any(|cell| claims(j).contains(&Some(*cell)));
+            swap || shared
+        };
+        let conflicting: Vec<bool> = (0..count)
+            .map(|i| moving[i] && (0..count).any(|j| j != i && moving[j] && conflicts(i, j)))
+            .collect();
+        for (moving, conflicting) in moving.iter_mut().zip(conflicting) {
+            *moving &= !conflicting;
+        }
+
+        // Robots that stay put block anything moving into their cell, which can stop a whole
+        // chain behind them.
+        loop {
+            let mut changed = false;
+            for i in 0..count {
+                if !moving[i] {
+                    continue;
+                }
+                let blocked =
+                    self.robots.iter().enumerate().any(|(j, robot)| {
+                        j != i && !moving[j] && claims(i).contains(&Some(robot.pos))
+                    });
+                if blocked {
+                    moving[i] = false;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        moving
+    }
+    /// Makes the moves `resolve_moves` allowed, pushing crates and picking up keys on the way.
+    pub fn apply_moves(&mut self, intents: &[Option<(i32, i32)>], moving: &[bool]) {
+        for (i, intent) in intents.iter().enumerate() {
+            let Some(to) = *intent else {
+                continue;
+            };
+            if !moving[i] {
+                continue;
+            }
+            let from = self.robots[i].pos;
+            if let Some(index) = self
+                .objects
+                .iter()
+                .position(|object| object.pos == to && object.kind == ObjectKind::Crate)
+            {
+                let beyond = (to.0 * 2 - from.0, to.1 * 2 - from.1);
+                if self.map.get_cell_type(beyond.0, beyond.1) == Cell::Gap {
+                    self.map.set_cell_type(beyond.0, beyond.1, Cell::Empty);
+                    self.objects.remove(index);
+                } else {
+                    self.objects[index].pos = beyond;
+                }
+            }
+            let keys = &mut self.keys;
+            self.objects.retain(|object| match object.kind {
+                ObjectKind::Key { channel } if object.pos == to => {
+                    keys[channel] = true;
+                    false
+                }
+                _ => true,
+            });
+            let robot = &mut self.robots[i];
+            robot.pos = to;
+            if self.map.get_cell_type(to.0, to.1) == Cell::Gap {
+                robot.brain.reader.halted = true;
+            }
+        }
+    }
+    /// Whether a crate can be pushed into `pos`, ignoring robots. Crates can't push each other,
+    /// and a crate pushed into a gap falls in and fills it.
+    fn crate_can_enter(&self, pos: (i32, i32)) -> bool {
+        self.map.get_cell_type(pos.0, pos.1) != Cell::Wall
+            && !self
+                .objects
+                .iter()
+                .any(|object| object.pos == pos && object.is_solid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cell, Object, ObjectKind, Robot, Rotation, World};
+
+    fn world(robots: &[(i32, i32)]) -> World {
+        World::new(
+            5,
+            5,
+            robots
+                .iter()
+                .map(|&pos| Robot::new(pos, Rotation::Up, 1, 1, vec![]))
+                .collect(),
+        )
+    }
+
+    fn step_moves(world: &mut World, intents: &[Option<(i32, i32)>]) {
+        let moving = world.resolve_moves(intents);
+        world.apply_moves(intents, &moving);
+    }
+
+    #[test]
+    fn single_move() {
+        let world = world(&[(1, 1)]);
+        assert_eq!(world.resolve_moves(&[Some((1, 2))]), vec![true]);
+    }
+
+    #[test]
+    fn wall_blocks() {
+        let mut world = world(&[(1, 1)]);
+        world.map.set_cell_type(1, 2, Cell::Wall);
+        assert_eq!(world.resolve_moves(&[Some((1, 2))]), vec![false]);
+        assert_eq!(world.resolve_moves(&[Some((1, 0))]), vec![true]);
+    }
+
+    #[test]
+    fn head_on_into_same_cell() {
+        let world = world(&[(0, 2), (2, 2)]);
+        let intents = [Some((1, 2)), Some((3, 2))];
+        assert_eq!(world.resolve_moves(&intents), vec![true, true]);
+        let intents = [Some((1, 2)), Some((1, 2))];
+        assert_eq!(world.resolve_moves(&intents), vec![false, false]);
+    }
+
+    #[test]
+    fn swap_fails() {
+        let world = world(&[(1, 2), (2, 2)]);
+        let intents = [Some((2, 2)), Some((1, 2))];
+        assert_eq!(world.resolve_moves(&intents), vec![false, false]);
+    }
+
+    #[test]
+    fn stationary_robot_blocks() {
+        let world = world(&[(1, 2), (2, 2)]);
+        assert_eq!(
+            world.resolve_moves(&[Some((2, 2)), None]),
+            vec![false, false]
+        );
+    }
+
+    #[test]
+    fn chain_moves_in_any_order() {
+        let world_forward = world(&[(0, 2), (1, 2), (2, 2)]);
+        let intents = [Some((1, 2)), Some((2, 2)), Some((3, 2))];
+        assert_eq!(world_forward.resolve_moves(&intents), vec![true; 3]);
+
+        let world_backward = world(&[(2, 2), (1, 2), (0, 2)]);
+        let intents = [Some((3, 2)), Some((2, 2)), Some((1, 2))];
+        assert_eq!(world_backward.resolve_moves(&intents), vec![true; 3]);
+    }
+
+    #[test]
+    fn blocked_chain_stops() {
+        let mut world = world(&[(2, 2), (3, 2), (4, 2)]);
+        world.map.set_cell_type(4, 3, Cell::Wall);
+        // The front robot walks into the wall, so nobody behind it can follow.
+        let intents = [Some((3, 2)), Some((4, 2)), Some((4, 3))];
+        assert_eq!(world.resolve_moves(&intents), vec![false; 3]);
+    }
+
+    #[test]
+    fn loop_moves_together() {
+        let world = world(&[(1, 1), (1, 2), (2, 2), (2, 1)]);
+        let intents = [Some((1, 2)), Some((2, 2)), Some((2, 1)), Some((1, 1))];
+        assert_eq!(world.resolve_moves(&intents), vec![true; 4]);
+    }
+
+    #[test]
+    fn crate_is_pushed() {
+        let mut world = world(&[(1, 1)]);
+        world.objects.push(Object::new(ObjectKind::Crate, (1, 2)));
+        step_moves(&mut world, &[Some((1, 2))]);
+        assert_eq!(world.robots[0].pos, (1, 2));
+        assert_eq!(world.objects[0].pos, (1, 3));
+    }
+
+    #[test]
+    fn crate_fills_gap() {
+        let mut world = world(&[(1, 1)]);
+        world.map.set_cell_type(1, 3, Cell::Gap);
+        world.objects.push(Object::new(ObjectKind::Crate, (1, 2)));
+        step_moves(&mut world, &[Some((1, 2))]);
+        assert!(world.objects.is_empty());
+        assert_eq!(world.map.get_cell_type(1, 3), Cell::Empty);
+    }
+
+    #[test]
+    fn crate_against_robot_or_crate_fails() {
+        let mut world = world(&[(1, 1), (1, 3)]);
+        world.objects.push(Object::new(ObjectKind::Crate, (1, 2)));
+        assert_eq!(
+            world.resolve_moves(&[Some((1, 2)), None]),
+            vec![false, false]
+        );
+        // The robot in the way is leaving, so the crate can follow it.
+        let intents = [Some((1, 2)), Some((1, 4))];
+        assert_eq!(world.resolve_moves(&intents), vec![true, true]);
+
+        world.robots.pop();
+        world.objects.push(Object::new(ObjectKind::Crate, (1, 3)));
+        assert_eq!(world.resolve_moves(&[Some((1, 2))]), vec![false]);
+    }
+
+    #[test]
+    fn crate_pushed_from_two_sides_fails() {
+        let mut world = world(&[(0, 2), (1, 1)]);
+        world.objects.push(Object::new(ObjectKind::Crate, (1, 2)));
+        let intents = [Some((1, 2)), Some((1, 2))];
+        assert_eq!(world.resolve_moves(&intents), vec![false, false]);
+    }
+
+    #[test]
+    fn crate_and_robot_into_same_cell_fails() {
+        let mut world = world(&[(0, 2), (2, 1)]);
+        world.objects.push(Object::new(ObjectKind::Crate, (1, 2)));
+        let intents = [Some((1, 2)), Some((2, 2))];
+        assert_eq!(world.resolve_moves(&intents), vec![false, false]);
+    }
+}
@@ -0,0 +1,52 @@
+use raylib::prelude::*;
+
+/// Seconds a notice stays on screen.
+const NOTICE_TIME: f32 = 4.0;
+
+/// A line of feedback at the bottom of the screen, e.g. that a file was saved or why an edit was
+/// refused.
+#[derive(Default)]
+pub struct Notice {
+    text: String,
+    error: bool,
+    time_left: f32,
+}
+
+impl Notice {
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.show(text.into(), false);
+    }
+    pub fn error(&mut self, text: impl ToString) {
+        self.show(text.to_string(), true);
+    }
+    fn show(&mut self, text: String, error: bool) {
+        self.text = text;
+        self.error = error;
+        self.time_left = NOTICE_TIME;
+    }
+    pub fn update(&mut self, dt: f32) {
+        self.time_left = (self.time_left - dt).max(0.0);
+    }
+}
+
+pub fn draw_notice(d: &mut RaylibDrawHandle, notice: &Notice) {
+    if notice.time_left <= 0.0 {
+        return;
+    }
+    let alpha = (notice.time_left.min(1.0) * 255.0) as u8;
+    let color = match notice.error {
+        true => Color::new(220, 80, 80, alpha),
+        false => Color::new(90, 200, 110, alpha),
+    };
+    let font_size = 16;
+    let y = d.get_screen_height() - font_size - 8;
+    let width = d.measure_text(&notice.text, font_size);
+    d.draw_rectangle(
+        6,
+        y - 4,
+        width + 8,
+        font_size + 8,
+        Color::new(20, 20, 20, alpha / 4 * 3),
+    );
+    d.draw_text(&notice.text, 10, y, font_size, color);
+}
@@ -0,0 +1,103 @@
+use std::{fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ron_file, Brain, FileError, Instruction, CHANNELS};
+
+/// The player-authored part of a `Brain`, saved so solutions can be kept, shared and used as
+/// regression fixtures.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Program {
+    pub width: u32,
+    pub height: u32,
+    pub instructions: Vec<Instruction>,
+}
+
+#[derive(Debug)]
+pub enum ProgramError {
+    File(FileError),
+    Invalid(String),
+}
+
+impl fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgramError::File(error) => write!(f, "program: {error}"),
+            ProgramError::Invalid(reason) => write!(f, "invalid program: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ProgramError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ProgramError::File(error) => Some(error),
+            ProgramError::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<FileError> for ProgramError {
+    fn from(error: FileError) -> Self {
+        ProgramError::File(error)
+    }
+}
+
+impl Program {
+    pub fn load(path: impl AsRef<Path>) -> Result<Program, ProgramError> {
+        Ok(ron_file::load(path)?)
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ProgramError> {
+        Ok(ron_file::save(self, path)?)
+    }
+}
+
+impl Brain {
+    pub fn to_program(&self) -> Program {
+        Program {
+            width: self.width,
+            height: self.height,
+            instructions: self.instructions.clone(),
+        }
+    }
+    /// Replaces the instructions of this brain with `program`, refusing programs that don't fit
+    /// the brain or use more instructions than the level allows.
+    pub fn apply_program(&mut self, program: Program) -> Result<(), ProgramError> {
+        if program.width != self.width || program.height != self.height {
+            return Err(ProgramError::Invalid(format!(
+                "program is {}x{} but the brain is {}x{}",
+                program.width, program.height, self.width, self.height
+            )));
+        }
+        if program.instructions.len() != (program.width * program.height) as usize {
+            return Err(ProgramError::Invalid(format!(
+                "program is {}x{} but has {} instructions",
+                program.width,
+                program.height,
+                program.instructions.len()
+            )));
+        }
+        if program
+            .instructions
+            .iter()
+            .any(|instruction| instruction.channel >= CHANNELS)
+        {
+            return Err(ProgramError::Invalid(format!(
+                "instructions can only use {CHANNELS} channels"
+            )));
+        }
+        if let Some(instruction_type) = self.over_budget(&program.instructions) {
+            return Err(ProgramError::Invalid(format!(
+                "program uses more {instruction_type:?} instructions than the level allows"
+            )));
+        }
+        self.instructions = program.instructions;
+        Ok(())
+    }
+    pub fn save_program(&self, path: impl AsRef<Path>) -> Result<(), ProgramError> {
+        self.to_program().save(path)
+    }
+    pub fn load_program(&mut self, path: impl AsRef<Path>) -> Result<(), ProgramError> {
+        self.apply_program(Program::load(path)?)
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{Brain, Instruction, InstructionType, Rotation};
+
+/// A rectangular block of instructions cut or copied out of a brain. Row y = 0 is the bottom of
+/// the block, like in a `Brain`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    pub width: u32,
+    pub height: u32,
+    pub instructions: Vec<Instruction>,
+}
+
+impl Region {
+    pub fn get_instruction(&self, pos: (i32, i32)) -> &Instruction {
+        &self.instructions[(pos.0 + pos.1 * self.width as i32) as usize]
+    }
+    /// Turns the block a quarter turn clockwise, turning the instructions in it along with it.
+    pub fn rotate_clockwise(&self) -> Region {
+        let mut instructions = Vec::with_capacity(self.instructions.len());
+        for y in 0..self.width as i32 {
+            for x in 0..self.height as i32 {
+                let mut instruction = *self.get_instruction((self.width as i32 - 1 - y, x));
+                if instruction.instruction_type.has_direction() {
+                    instruction.rotation = match instruction.rotation {
+                        Rotation::Up => Rotation::Right,
+                        Rotation::Right => Rotation::Down,
+                        Rotation::Down => Rotation::Left,
+                        Rotation::Left => Rotation::Up,
+                    };
+                }
+                instructions.push(instruction);
+            }
+        }
+        Region {
+            width: self.height,
+            height: self.width,
+            instructions,
+        }
+    }
+    pub fn rotate_counter_clockwise(&self) -> Region {
+        self.rotate_clockwise()
+            .rotate_clockwise()
+            .rotate_clockwise()
+    }
+}
+
+impl InstructionType {
+    /// Whether the instruction's rotation is a direction rather than unused.
+    pub fn has_direction(&self) -> bool {
+        matches!(
+            self,
+            InstructionType::Direction
+                | InstructionType::WallAhead
+                | InstructionType::GapAhead
+                | InstructionType::OnGoal
+                | InstructionType::BranchIfZero
+        )
+    }
+}
+
+impl Brain {
+    /// Copies the instructions between the corners `from` and `to`, both included. Corners
+    /// outside the brain are clamped to its edges.
+    pub fn region(&self, from: (i32, i32), to: (i32, i32)) -> Region {
+        let clamp = |pos: (i32, i32)| {
+            (
+                pos.0.clamp(0, self.width as i32 - 1),
+                pos.1.clamp(0, self.height as i32 - 1),
+            )
+        };
+        let (from, to) = (clamp(from), clamp(to));
+        let (min, max) = (
+            (from.0.min(to.0), from.1.min(to.1)),
+            (from.0.max(to.0), from.1.max(to.1)),
+        );
+        let mut instructions = vec![];
+        for y in min.1..=max.1 {
+            for x in min.0..=max.0 {
+                instructions.push(*self.get_instruction((x, y)));
+            }
+        }
+        Region {
+            width: (max.0 - min.0 + 1) as u32,
+            height: (max.1 - min.1 + 1) as u32,
+            instructions,
+        }
+    }
+    /// Places `region` with its bottom left corner at `pos`, dropping whatever falls outside the
+    /// brain. Refuses with the instruction type the budget runs out of if the result would use
+    /// more instructions than the level allows, leaving the brain unchanged.
+    pub fn paste(&mut self, region: &Region, pos: (i32, i32)) -> Result<(), InstructionType> {
+        let mut instructions = self.instructions.clone();
+        for y in 0..region.height as i32 {
+            for x in 0..region.width as i32 {
+                let target = (pos.0 + x, pos.1 + y);
+                if self.in_bounds(target) {
+                    let index = (target.0 + target.1 * self.width as i32) as usize;
+                    instructions[index] = *region.get_instruction((x, y));
+                }
+            }
+        }
+        if let Some(instruction_type) = self.over_budget(&instructions) {
+            return Err(instruction_type);
+        }
+        self.instructions = instructions;
+        Ok(())
+    }
+    /// Empties the instructions between the corners `from` and `to`, both included.
+    pub fn clear_region(&mut self, from: (i32, i32), to: (i32, i32)) {
+        for y in from.1.min(to.1)..=from.1.max(to.1) {
+            for x in from.0.min(to.0)..=from.0.max(to.0) {
+                if self.in_bounds((x, y)) {
+                    let index = (x + y * self.width as i32) as usize;
+                    self.instructions[index].instruction_type = InstructionType::None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Instruction, InstructionType, Region, Rotation};
+
+    #[test]
+    fn turning_keeps_wait_length() {
+        let wait = Instruction {
+            instruction_type: InstructionType::Wait,
+            rotation: Rotation::Up,
+            edit: true,
+            channel: 2,
+        };
+        let region = Region {
+            width: 1,
+            height: 1,
+            instructions: vec![wait],
+        };
+        let turned = region.rotate_clockwise();
+        assert_eq!(turned.instructions[0].wait_ticks(), 3);
+        assert_eq!(turned.rotate_counter_clockwise(), region);
+    }
+}
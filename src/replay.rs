@@ -0,0 +1,183 @@
+use raylib::prelude::*;
+use robot_coding_game::{LevelError, Trace, TraceTick, World};
+
+use crate::{draw_board, draw_brain, Assets, BoardView, BrainEdit, Notice};
+
+/// Where `Ctrl+S` in the replay writes the trace, and `Ctrl+L` reads it back from.
+pub const TRACE_PATH: &str = "traces/trace.ron";
+
+/// Steps forwards and backwards through a recorded run. The world is simulated again from the
+/// level and programs stored in the trace, with the brain edits made during the run, and the
+/// trace says what each robot did on the way.
+pub struct Replay {
+    trace: Trace,
+    /// Ticks shown so far, 0 being the world before the first tick.
+    tick: usize,
+    world: World,
+    /// Index of the robot whose brain is shown.
+    robot: usize,
+}
+
+impl Replay {
+    pub fn new(trace: Trace, robot: usize) -> Result<Replay, LevelError> {
+        let world = trace.start()?;
+        Ok(Replay {
+            robot: robot.min(world.robots.len().saturating_sub(1)),
+            world,
+            trace,
+            tick: 0,
+        })
+    }
+    fn go_to(&mut self, tick: usize) -> Result<(), LevelError> {
+        if tick < self.tick {
+            self.world = self.trace.start()?;
+            self.tick = 0;
+        }
+        while self.tick < tick.min(self.trace.ticks.len()) {
+            self.trace.replay_tick(&mut self.world, self.tick)?;
+            self.tick += 1;
+        }
+        Ok(())
+    }
+    /// What happened during the tick that led to the shown world.
+    fn current(&self) -> Option<&TraceTick> {
+        self.tick.checked_sub(1).map(|tick| &self.trace.ticks[tick])
+    }
+}
+
+/// Stepping with the arrow keys, ten ticks at a time with Shift, jumping to either end with Home
+/// and End, switching robots with Tab, and saving and loading the trace with Ctrl+S and Ctrl+L.
+/// Returns whether the replay should be closed.
+pub fn replay_inputs(rl: &RaylibHandle, replay: &mut Replay, notice: &mut Notice) -> bool {
+    let ctrl = rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL)
+        || rl.is_key_down(KeyboardKey::KEY_RIGHT_CONTROL);
+    let shift =
+        rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) || rl.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT);
+    let step = match shift {
+        true => 10,
+        false => 1,
+    };
+    let mut target = None;
+    if rl.is_key_pressed(KeyboardKey::KEY_RIGHT) {
+        target = Some(replay.tick + step);
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_LEFT) {
+        target = Some(replay.tick.saturating_sub(step));
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_HOME) {
+        target = Some(0);
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_END) {
+        target = Some(replay.trace.ticks.len());
+    }
+    if let Some(Err(error)) = target.map(|tick| replay.go_to(tick)) {
+        notice.error(format!("trace: {error}"));
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_TAB) && !replay.world.robots.is_empty() {
+        replay.robot = (replay.robot + 1) % replay.world.robots.len();
+    }
+    if ctrl && rl.is_key_pressed(KeyboardKey::KEY_S) {
+        match replay.trace.save(TRACE_PATH) {
+            Ok(()) => notice.info(format!("Saved trace to {TRACE_PATH}")),
+            Err(error) => notice.error(format!("trace: {error}")),
+        }
+    }
+    if ctrl && rl.is_key_pressed(KeyboardKey::KEY_L) {
+        match load_replay(replay.robot) {
+            Ok(loaded) => {
+                *replay = loaded;
+                notice.info(format!("Loaded trace from {TRACE_PATH}"));
+            }
+            Err(error) => notice.error(error),
+        }
+    }
+    rl.is_key_pressed(KeyboardKey::KEY_F3) || rl.is_key_pressed(KeyboardKey::KEY_ESCAPE)
+}
+
+/// Opens the trace saved at `TRACE_PATH`.
+pub fn load_replay(robot: usize) -> Result<Replay, String> {
+    let trace = Trace::load(TRACE_PATH).map_err(|error| format!("trace: {error}"))?;
+    Replay::new(trace, robot).map_err(|error| format!("trace: {error}"))
+}
+
+pub fn draw_replay(
+    d: &mut RaylibDrawHandle,
+    replay: &Replay,
+    assets: &Assets,
+    board: &BoardView,
+    brain_edit: &BrainEdit,
+    mouse_pos: Vector2,
+) {
+    draw_board(
+        d,
+        &replay.world,
+        assets,
+        board.pos,
+        board.width,
+        board.scale,
+        1.0,
+    );
+    let truncated = match replay.trace.truncated {
+        true => " (truncated)",
+        false => "",
+    };
+    d.draw_text(
+        &format!(
+            "Replay - tick {}/{}{truncated}",
+            replay.tick,
+            replay.trace.ticks.len()
+        ),
+        10,
+        10,
+        20,
+        Color::WHITE,
+    );
+    d.draw_text(
+        "Left/Right step, Shift x10, Home/End, Tab robot, Ctrl+S/L save/load trace, F3 back",
+        10,
+        32,
+        10,
+        Color::LIGHTGRAY,
+    );
+    let Some(robot) = replay.world.robots.get(replay.robot) else {
+        return;
+    };
+    let robot_trace = replay
+        .current()
+        .and_then(|tick| tick.robots.get(replay.robot));
+    let action = match robot_trace {
+        None => "not started".to_string(),
+        Some(robot_trace) => {
+            let instruction = match robot_trace.instruction {
+                Some(instruction_type) => format!("{instruction_type:?}"),
+                None => "nothing".to_string(),
+            };
+            match robot_trace.blocked {
+                Some(pos) => format!("ran {instruction}, blocked moving to {pos:?}"),
+                None => format!("ran {instruction}"),
+            }
+        }
+    };
+    d.draw_text(
+        &format!("Robot {}: {action}", replay.robot + 1),
+        10,
+        44,
+        16,
+        Color::new(255, 220, 60, 255),
+    );
+    draw_brain(
+        d,
+        &robot.brain,
+        robot.carrying,
+        None,
+        robot_trace
+            .filter(|robot_trace| robot_trace.instruction.is_some())
+            .map(|robot_trace| robot_trace.reader_pos),
+        brain_edit.pos,
+        brain_edit.size,
+        assets,
+        mouse_pos,
+        &brain_edit.selected_instruction,
+        brain_edit.scale,
+    );
+}
@@ -0,0 +1,118 @@
+use raylib::prelude::*;
+use robot_coding_game::Score;
+
+/// Number of bars in each histogram, at most.
+const MAX_BINS: u32 = 10;
+
+/// Shows the score of a finished run next to the player's best and a histogram of the level's
+/// reference scores for each metric.
+pub fn draw_results(
+    d: &mut RaylibDrawHandle,
+    score: Score,
+    best: Option<Score>,
+    reference_scores: &[Score],
+) {
+    let panel = Rectangle {
+        x: d.get_screen_width() as f32 / 2.0 - 180.0,
+        y: 60.0,
+        width: 360.0,
+        height: 330.0,
+    };
+    d.draw_rectangle_rec(panel, Color::new(30, 30, 30, 230));
+    d.draw_rectangle_lines_ex(panel, 2.0, Color::GRAY);
+    d.draw_text(
+        "Results",
+        panel.x as i32 + 10,
+        panel.y as i32 + 10,
+        20,
+        Color::WHITE,
+    );
+    let metrics: [(&str, fn(&Score) -> u32); 3] = [
+        ("Ticks", |score| score.ticks),
+        ("Instructions", |score| score.instructions as u32),
+        ("Area", |score| score.area),
+    ];
+    for (i, (name, metric)) in metrics.into_iter().enumerate() {
+        let y = panel.y + 40.0 + i as f32 * 95.0;
+        let text = match best {
+            Some(best) => format!("{name}: {} (best {})", metric(&score), metric(&best)),
+            None => format!("{name}: {}", metric(&score)),
+        };
+        d.draw_text(&text, panel.x as i32 + 10, y as i32, 16, Color::WHITE);
+        draw_histogram(
+            d,
+            Rectangle {
+                x: panel.x + 10.0,
+                y: y + 20.0,
+                width: panel.width - 20.0,
+                height: 65.0,
+            },
+            metric(&score),
+            &reference_scores.iter().map(metric).collect::<Vec<_>>(),
+        );
+    }
+}
+
+/// Bars counting how many reference values fall in each range. The bar holding `value` is
+/// highlighted.
+fn draw_histogram(d: &mut RaylibDrawHandle, rec: Rectangle, value: u32, references: &[u32]) {
+    if references.is_empty() {
+        d.draw_text(
+            "No reference scores",
+            rec.x as i32,
+            rec.y as i32 + 4,
+            12,
+            Color::GRAY,
+        );
+        return;
+    }
+    let low = references.iter().copied().chain([value]).min().unwrap();
+    let high = references.iter().copied().chain([value]).max().unwrap();
+    let range = high - low + 1;
+    let bins = range.min(MAX_BINS);
+    let bin_of = |value: u32| ((value - low) * bins / range) as usize;
+    let mut counts = vec![0; bins as usize];
+    for &reference in references {
+        counts[bin_of(reference)] += 1;
+    }
+    let max_count = counts.iter().copied().max().unwrap_or(0).max(1);
+    let label_height = 12.0;
+    let bar_width = rec.width / bins as f32;
+    for (bin, &count) in counts.iter().enumerate() {
+        let bar_height = (rec.height - label_height) * count as f32 / max_count as f32;
+        let color = match bin == bin_of(value) {
+            true => Color::new(90, 200, 110, 255),
+            false => Color::new(110, 110, 110, 255),
+        };
+        d.draw_rectangle_rec(
+            Rectangle {
+                x: rec.x + bin as f32 * bar_width + 1.0,
+                y: rec.y + rec.height - label_height - bar_height,
+                width: bar_width - 2.0,
+                height: bar_height,
+            },
+            color,
+        );
+    }
+    d.draw_line_v(
+        Vector2::new(rec.x, rec.y + rec.height - label_height),
+        Vector2::new(rec.x + rec.width, rec.y + rec.height - label_height),
+        Color::GRAY,
+    );
+    d.draw_text(
+        &low.to_string(),
+        rec.x as i32,
+        (rec.y + rec.height - label_height) as i32 + 2,
+        10,
+        Color::LIGHTGRAY,
+    );
+    let high_text = high.to_string();
+    let high_width = d.measure_text(&high_text, 10);
+    d.draw_text(
+        &high_text,
+        (rec.x + rec.width) as i32 - high_width,
+        (rec.y + rec.height - label_height) as i32 + 2,
+        10,
+        Color::LIGHTGRAY,
+    );
+}
@@ -0,0 +1,68 @@
+use std::{fmt, fs, path::Path};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Why a RON file couldn't be read or written.
+#[derive(Debug)]
+pub enum FileError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileError::Io(error) => write!(f, "could not access file: {error}"),
+            FileError::Parse(error) => write!(f, "could not parse file: {error}"),
+            FileError::Serialize(error) => write!(f, "could not write file: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FileError::Io(error) => Some(error),
+            FileError::Parse(error) => Some(error),
+            FileError::Serialize(error) => Some(error),
+        }
+    }
+}
+
+impl From<std::io::Error> for FileError {
+    fn from(error: std::io::Error) -> Self {
+        FileError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for FileError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        FileError::Parse(error)
+    }
+}
+
+impl From<ron::Error> for FileError {
+    fn from(error: ron::Error) -> Self {
+        FileError::Serialize(error)
+    }
+}
+
+pub(crate) fn load<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, FileError> {
+    parse(&fs::read_to_string(path)?)
+}
+
+pub(crate) fn parse<T: DeserializeOwned>(source: &str) -> Result<T, FileError> {
+    Ok(ron::from_str(source)?)
+}
+
+/// Writes `value` as pretty RON, creating the directories leading up to `path`.
+pub(crate) fn save<T: Serialize>(value: &T, path: impl AsRef<Path>) -> Result<(), FileError> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let source = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())?;
+    fs::write(path, source)?;
+    Ok(())
+}
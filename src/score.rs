@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Brain, InstructionType, World};
+
+/// How good a solution is. Lower is better for every metric.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Score {
+    /// Ticks the level took to complete.
+    pub ticks: u32,
+    /// Instructions placed across every robot's brain.
+    pub instructions: usize,
+    /// Brain cells inside the bounding boxes of the placed instructions, summed over robots.
+    pub area: u32,
+}
+
+impl Score {
+    /// Keeps the better value of each metric, which may come from different solutions.
+    pub fn best(self, other: Score) -> Score {
+        Score {
+            ticks: self.ticks.min(other.ticks),
+            instructions: self.instructions.min(other.instructions),
+            area: self.area.min(other.area),
+        }
+    }
+}
+
+impl World {
+    /// Scores the programs in this world, using the ticks simulated so far.
+    pub fn score(&self) -> Score {
+        Score {
+            ticks: self.ticks,
+            instructions: self
+                .robots
+                .iter()
+                .map(|robot| robot.brain.placed_instructions())
+                .sum(),
+            area: self
+                .robots
+                .iter()
+                .map(|robot| robot.brain.used_area())
+                .sum(),
+        }
+    }
+}
+
+impl Brain {
+    /// Number of cells that hold an instruction.
+    pub fn placed_instructions(&self) -> usize {
+        self.instructions
+            .iter()
+            .filter(|instruction| instruction.instruction_type != InstructionType::None)
+            .count()
+    }
+    /// Area of the smallest rectangle containing every placed instruction.
+    pub fn used_area(&self) -> u32 {
+        let mut bounds: Option<((u32, u32), (u32, u32))> = None;
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            if instruction.instruction_type == InstructionType::None {
+                continue;
+            }
+            let pos = (i as u32 % self.width, i as u32 / self.width);
+            bounds = Some(match bounds {
+                None => (pos, pos),
+                Some((min, max)) => (
+                    (min.0.min(pos.0), min.1.min(pos.1)),
+                    (max.0.max(pos.0), max.1.max(pos.1)),
+                ),
+            });
+        }
+        bounds.map_or(0, |(min, max)| (max.0 - min.0 + 1) * (max.1 - min.1 + 1))
+    }
+}
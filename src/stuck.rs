@@ -0,0 +1,314 @@
+use std::fmt;
+
+use crate::{
+    Brain, Cell, Instruction, InstructionType, Item, ObjectKind, Reader, Rotation, Signals, World,
+    CHANNELS,
+};
+
+/// Why a run ended without the level being solved or failed. The simulation is deterministic,
+/// so once the world is back in a state it was in before it will repeat forever.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stuck {
+    /// The robots repeat the same moves forever.
+    Loop,
+    /// The reader of `robot` faces a `None` cell or the edge of the brain at `pos` and can't go
+    /// on.
+    Stalled { robot: usize, pos: (i32, i32) },
+    /// The reader of `robot` listens for a signal on `channel` that no robot is left to raise.
+    WaitingForSignal { robot: usize, channel: usize },
+    /// Every robot halted.
+    Halted,
+}
+
+impl fmt::Display for Stuck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stuck::Loop => write!(f, "loops forever"),
+            Stuck::Stalled { robot, pos } => {
+                write!(f, "robot {} stalled at ({}, {})", robot + 1, pos.0, pos.1)
+            }
+            Stuck::WaitingForSignal { robot, channel } => write!(
+                f,
+                "robot {} waits for signal {} that never comes",
+                robot + 1,
+                channel + 1
+            ),
+            Stuck::Halted => write!(f, "every robot halted"),
+        }
+    }
+}
+
+/// Finds out when the world comes back to an earlier state. Only one state is kept, and it is
+/// replaced after twice as many ticks each time, so a loop is found within a few times its
+/// length after it starts.
+///
+/// Counters that keep growing make every state different, so such loops only show up once the
+/// counter reaches `MAX_COUNTER`.
+#[derive(Clone, Debug, Default)]
+pub struct LoopCheck {
+    saved: Option<Configuration>,
+    /// Ticks since `saved` was taken.
+    age: u32,
+    /// Ticks to wait before `saved` is replaced.
+    limit: u32,
+}
+
+/// Everything that decides what the world does next. Leaves out tick counters and the
+/// positions kept for interpolation.
+#[derive(Clone, Debug)]
+struct Configuration {
+    robots: Vec<RobotConfiguration>,
+    cells: Vec<Cell>,
+    items: Vec<((i32, i32), Item)>,
+    objects: Vec<(ObjectKind, (i32, i32))>,
+    keys: [bool; CHANNELS],
+    signals: Signals,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct RobotConfiguration {
+    pos: (i32, i32),
+    rotation: Rotation,
+    carrying: Option<Item>,
+    reader: ReaderConfiguration,
+    counters: [u32; CHANNELS],
+    instructions: Vec<Instruction>,
+}
+
+/// A `Reader` without its cycle count.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ReaderConfiguration {
+    pos: (i32, i32),
+    rotation: Rotation,
+    read_next: bool,
+    halted: bool,
+    waiting: u32,
+}
+
+impl ReaderConfiguration {
+    fn of(reader: &Reader) -> ReaderConfiguration {
+        ReaderConfiguration {
+            pos: reader.pos,
+            rotation: reader.rotation,
+            read_next: reader.read_next,
+            halted: reader.halted,
+            waiting: reader.waiting,
+        }
+    }
+}
+
+impl Configuration {
+    fn of(world: &World) -> Configuration {
+        Configuration {
+            robots: world
+                .robots
+                .iter()
+                .map(|robot| RobotConfiguration {
+                    pos: robot.pos,
+                    rotation: robot.rotation,
+                    carrying: robot.carrying,
+                    reader: ReaderConfiguration::of(&robot.brain.reader),
+                    counters: robot.brain.counters,
+                    instructions: robot.brain.instructions.clone(),
+                })
+                .collect(),
+            cells: world.map.cells.clone(),
+            items: world.map.items.clone(),
+            objects: world
+                .objects
+                .iter()
+                .map(|object| (object.kind, object.pos))
+                .collect(),
+            keys: world.keys,
+            signals: world.signals,
+        }
+    }
+    /// Compares against `world` without copying it, since this runs every tick.
+    fn matches(&self, world: &World) -> bool {
+        self.robots.len() == world.robots.len()
+            && self.robots.iter().zip(&world.robots).all(|(saved, robot)| {
+                saved.pos == robot.pos
+                    && saved.rotation == robot.rotation
+                    && saved.carrying == robot.carrying
+                    && saved.reader == ReaderConfiguration::of(&robot.brain.reader)
+                    && saved.counters == robot.brain.counters
+                    && saved.instructions == robot.brain.instructions
+            })
+            && self.cells == world.map.cells
+            // Items can be picked up and put down in a different order, so they're compared
+            // as a set. There is at most one item per cell.
+            && self.items.len() == world.map.items.len()
+            && self.items.iter().all(|item| world.map.items.contains(item))
+            && self.objects.len() == world.objects.len()
+            && self
+                .objects
+                .iter()
+                .zip(&world.objects)
+                .all(|(saved, object)| *saved == (object.kind, object.pos))
+            && self.keys == world.keys
+            && self.signals == world.signals
+    }
+}
+
+impl LoopCheck {
+    /// Returns whether `world` is in a state it has been in before. Call once per tick.
+    pub fn repeats(&mut self, world: &World) -> bool {
+        if self
+            .saved
+            .as_ref()
+            .is_some_and(|saved| saved.matches(world))
+        {
+            return true;
+        }
+        self.age += 1;
+        if self.age >= self.limit {
+            self.saved = Some(Configuration::of(world));
+            self.age = 0;
+            self.limit = (self.limit * 2).max(1);
+        }
+        false
+    }
+}
+
+impl World {
+    /// Works out why the world stopped changing, assuming it has. While any reader still moves
+    /// on, the robots loop. Otherwise a stalled reader is reported first since it is usually the
+    /// reason the others wait for a signal.
+    pub fn stuck(&self) -> Stuck {
+        let advancing = self.robots.iter().any(|robot| {
+            !robot.brain.reader.halted
+                && !robot.brain.is_stalled()
+                && robot.brain.awaited_signal(&self.signals).is_none()
+        });
+        if advancing {
+            return Stuck::Loop;
+        }
+        if let Some((robot, stalled)) = self
+            .robots
+            .iter()
+            .enumerate()
+            .find(|(_, robot)| robot.brain.is_stalled())
+        {
+            return Stuck::Stalled {
+                robot,
+                pos: stalled.brain.reader.pos,
+            };
+        }
+        if let Some((robot, channel)) = self
+            .robots
+            .iter()
+            .enumerate()
+            .find_map(|(i, robot)| Some((i, robot.brain.awaited_signal(&self.signals)?)))
+        {
+            return Stuck::WaitingForSignal { robot, channel };
+        }
+        Stuck::Halted
+    }
+}
+
+impl Brain {
+    /// Whether the reader can't advance onto another instruction and will stay where it is for
+    /// the rest of the run.
+    pub fn is_stalled(&self) -> bool {
+        let reader = &self.reader;
+        !reader.read_next && !reader.halted && reader.waiting == 0
+    }
+    /// The channel the reader listens on, if it sits on a `WaitForSignal` that won't let it go
+    /// on the next tick.
+    fn awaited_signal(&self, signals: &Signals) -> Option<usize> {
+        let instruction = self.get_instruction(self.reader.pos);
+        (self.pending_instruction() == Some(InstructionType::WaitForSignal)
+            && !signals.current[instruction.channel])
+            .then_some(instruction.channel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{InstructionType, Level, LevelStatus, Stuck, World};
+
+    const TWO_ROBOTS: &str = "(
+        map: (width: 3, height: 3, cells: [Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty]),
+        robots: [
+            (pos: (0, 0), rotation: Up, brain_width: 1, brain_height: 1, total_instructions: []),
+            (pos: (2, 2), rotation: Up, brain_width: 1, brain_height: 1, total_instructions: []),
+        ],
+    )";
+
+    /// Two robots with one-cell brains holding `first` and `second`, the second one wrapping
+    /// when `wrap` is set so its reader never leaves the cell.
+    fn world(first: InstructionType, second: InstructionType, wrap: bool) -> World {
+        let mut world = Level::parse(TWO_ROBOTS).unwrap().to_world();
+        world.robots[0].brain.instructions[0].instruction_type = first;
+        world.robots[1].brain.instructions[0].instruction_type = second;
+        world.robots[1].brain.wrap = wrap;
+        world
+    }
+
+    fn run(mut world: World) -> LevelStatus {
+        for _ in 0..10_000 {
+            world.step();
+        }
+        world.status
+    }
+
+    #[test]
+    fn spinning_robot_loops() {
+        let world = world(InstructionType::Halt, InstructionType::RotateRight, true);
+        assert_eq!(run(world), LevelStatus::Stuck(Stuck::Loop));
+    }
+
+    #[test]
+    fn stalled_robot_next_to_a_looping_one_loops() {
+        let world = world(InstructionType::Blank, InstructionType::RotateRight, true);
+        assert_eq!(run(world), LevelStatus::Stuck(Stuck::Loop));
+    }
+
+    #[test]
+    fn incrementing_robot_loops() {
+        let world = world(InstructionType::Halt, InstructionType::Increment, true);
+        assert_eq!(run(world), LevelStatus::Stuck(Stuck::Loop));
+    }
+
+    #[test]
+    fn stalled_robot_is_reported() {
+        let world = world(InstructionType::Halt, InstructionType::Blank, false);
+        assert_eq!(
+            run(world),
+            LevelStatus::Stuck(Stuck::Stalled {
+                robot: 1,
+                pos: (0, 0)
+            })
+        );
+    }
+
+    #[test]
+    fn halted_robots_are_reported() {
+        let world = world(InstructionType::Halt, InstructionType::Halt, false);
+        assert_eq!(run(world), LevelStatus::Stuck(Stuck::Halted));
+    }
+
+    #[test]
+    fn unanswered_signal_wait_is_reported() {
+        let mut world = world(InstructionType::Halt, InstructionType::WaitForSignal, false);
+        world.robots[1].brain.instructions[0].channel = 2;
+        assert_eq!(
+            run(world),
+            LevelStatus::Stuck(Stuck::WaitingForSignal {
+                robot: 1,
+                channel: 2
+            })
+        );
+    }
+
+    #[test]
+    fn answered_signal_wait_loops() {
+        let mut world = world(
+            InstructionType::Signal,
+            InstructionType::WaitForSignal,
+            true,
+        );
+        world.robots[0].brain.wrap = true;
+        assert_eq!(run(world), LevelStatus::Stuck(Stuck::Loop));
+    }
+}
@@ -0,0 +1,203 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ron_file, FileError, Instruction, InstructionType, Level, LevelError, Program, Reader, Robot,
+    Rotation, World,
+};
+
+/// Every tick of a run, recorded while `World::trace` is set, so the run can be stepped through
+/// afterwards to find out why the robots did what they did. The level and programs are kept
+/// with the ticks, so a saved trace can be replayed on its own.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Trace {
+    pub level: Level,
+    /// Each robot's program when the run started.
+    pub programs: Vec<Program>,
+    pub ticks: Vec<TraceTick>,
+    /// Set once the run goes on for longer than `Trace::MAX_TICKS`. Later ticks aren't recorded.
+    #[serde(default)]
+    pub truncated: bool,
+    /// Each robot's instructions as of the last recorded tick, to notice edits during the run.
+    #[serde(skip)]
+    latest: Vec<Vec<Instruction>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TraceTick {
+    /// Programs changed since the previous tick, by robot index.
+    #[serde(default)]
+    pub edits: Vec<(usize, Program)>,
+    /// One entry per robot, in the same order as `World::robots`.
+    pub robots: Vec<RobotTrace>,
+}
+
+/// What one robot did during one tick.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RobotTrace {
+    /// Where the reader was at the start of the tick.
+    pub reader_pos: (i32, i32),
+    pub reader_rotation: Rotation,
+    /// The instruction that ran, or `None` while the reader was waiting, halted or stuck.
+    pub instruction: Option<InstructionType>,
+    /// Where the robot is at the end of the tick.
+    pub pos: (i32, i32),
+    pub rotation: Rotation,
+    /// The cell the robot tried to move into but couldn't.
+    pub blocked: Option<(i32, i32)>,
+}
+
+impl Trace {
+    /// Longest run that is recorded, so a robot stuck in a loop at max speed can't use up all
+    /// the memory.
+    pub const MAX_TICKS: usize = 100_000;
+
+    /// Starts a trace of a run of `level` that begins at `world`.
+    pub fn new(level: Level, world: &World) -> Trace {
+        Trace {
+            level,
+            programs: world
+                .robots
+                .iter()
+                .map(|robot| robot.brain.to_program())
+                .collect(),
+            ticks: vec![],
+            truncated: false,
+            latest: world
+                .robots
+                .iter()
+                .map(|robot| robot.brain.instructions.clone())
+                .collect(),
+        }
+    }
+    pub fn load(path: impl AsRef<Path>) -> Result<Trace, FileError> {
+        ron_file::load(path)
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FileError> {
+        ron_file::save(self, path)
+    }
+    /// The world as it was before the first tick of the run.
+    pub fn start(&self) -> Result<World, LevelError> {
+        self.level.validate()?;
+        let mut world = self.level.to_world();
+        if self.programs.len() != world.robots.len() {
+            return Err(LevelError::Invalid(format!(
+                "trace has {} programs but the level has {} robots",
+                self.programs.len(),
+                world.robots.len()
+            )));
+        }
+        for (i, (robot, program)) in world.robots.iter_mut().zip(&self.programs).enumerate() {
+            apply(robot, i, program)?;
+        }
+        Ok(world)
+    }
+    /// Runs tick number `tick` of the recorded run on `world`, which has to be the world the
+    /// ticks before it left behind. Brain edits made before the tick are applied first.
+    pub fn replay_tick(&self, world: &mut World, tick: usize) -> Result<(), LevelError> {
+        for (i, program) in &self.ticks[tick].edits {
+            let robot = world.robots.get_mut(*i).ok_or_else(|| {
+                LevelError::Invalid(format!("trace edits robot {i} which does not exist"))
+            })?;
+            apply(robot, *i, program)?;
+        }
+        world.step();
+        Ok(())
+    }
+    /// Programs that changed since the last tick was recorded.
+    pub(crate) fn edits(&mut self, robots: &[Robot]) -> Vec<(usize, Program)> {
+        self.latest.resize(robots.len(), vec![]);
+        let mut edits = vec![];
+        for (i, robot) in robots.iter().enumerate() {
+            if self.latest[i] != robot.brain.instructions {
+                self.latest[i] = robot.brain.instructions.clone();
+                edits.push((i, robot.brain.to_program()));
+            }
+        }
+        edits
+    }
+    /// Adds a tick given the edits made and the readers as they were before it, and the moves
+    /// made during it.
+    pub(crate) fn record(
+        &mut self,
+        edits: Vec<(usize, Program)>,
+        readers: &[(Reader, Option<InstructionType>)],
+        robots: &[Robot],
+        intents: &[Option<(i32, i32)>],
+        moving: &[bool],
+    ) {
+        if self.ticks.len() >= Trace::MAX_TICKS {
+            self.truncated = true;
+            return;
+        }
+        let robots = robots
+            .iter()
+            .enumerate()
+            .map(|(i, robot)| RobotTrace {
+                reader_pos: readers[i].0.pos,
+                reader_rotation: readers[i].0.rotation,
+                instruction: readers[i].1,
+                pos: robot.pos,
+                rotation: robot.rotation,
+                blocked: intents[i].filter(|_| !moving[i]),
+            })
+            .collect();
+        self.ticks.push(TraceTick { edits, robots });
+    }
+}
+
+fn apply(robot: &mut Robot, i: usize, program: &Program) -> Result<(), LevelError> {
+    robot
+        .brain
+        .apply_program(program.clone())
+        .map_err(|error| LevelError::Invalid(format!("robot {i}: {error}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{InstructionType, Level, Rotation, Trace};
+
+    const LEVEL: &str = "(
+        map: (width: 3, height: 3, cells: [Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty]),
+        robots: [
+            (pos: (0, 0), rotation: Up, brain_width: 2, brain_height: 3, total_instructions: [(4, Move), (2, RotateRight)]),
+        ],
+        brain_wrap: true,
+    )";
+
+    #[test]
+    fn saved_trace_replays_the_run() {
+        let level = Level::parse(LEVEL).unwrap();
+        let mut world = level.to_world();
+        let brain = &mut world.robots[0].brain;
+        brain.instructions[0].instruction_type = InstructionType::Move;
+        brain.instructions[2].instruction_type = InstructionType::Move;
+        world.trace = Some(Trace::new(level, &world));
+        world.step();
+        world.step();
+        // Edited during the run, so the replay has to pick the edit up
+        world.robots[0].brain.instructions[4].instruction_type = InstructionType::RotateRight;
+        for _ in 0..4 {
+            world.step();
+        }
+        let trace = world.trace.take().unwrap();
+        assert_eq!(trace.ticks[2].edits.len(), 1);
+
+        let path = std::env::temp_dir().join("robot_coding_game_trace_test.ron");
+        trace.save(&path).unwrap();
+        let loaded = Trace::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.programs, trace.programs);
+        assert_eq!(loaded.ticks, trace.ticks);
+
+        let mut replayed = loaded.start().unwrap();
+        for (tick, recorded) in loaded.ticks.iter().enumerate() {
+            loaded.replay_tick(&mut replayed, tick).unwrap();
+            assert_eq!(replayed.robots[0].pos, recorded.robots[0].pos);
+            assert_eq!(replayed.robots[0].rotation, recorded.robots[0].rotation);
+        }
+        assert_eq!(replayed.robots[0].pos, world.robots[0].pos);
+        assert_eq!(replayed.robots[0].rotation, Rotation::Right);
+    }
+}